@@ -13,7 +13,7 @@ fn main() {
 
     // Generate GLES 2.0 bindings
     let dest = PathBuf::from(&env::var("OUT_DIR").unwrap());
-    let mut file = File::create(&dest.join("gl_bindings.rs")).unwrap();
+    let mut file = File::create(dest.join("gl_bindings.rs")).unwrap();
     Registry::new(Api::Gles2, (2, 0), Profile::Core, Fallbacks::All, [])
         .write_bindings(StructGenerator, &mut file)
         .unwrap();
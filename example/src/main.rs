@@ -10,26 +10,26 @@ pub fn main() {
     let duck = trs_24::renderer::Model::new(
         include_bytes!("../../assets/models/duck/scene.gltf"),
         include_bytes!("../../assets/models/duck/scene.bin"),
-        include_bytes!("../../assets/models/duck/texture.png"),
     )
+    .set_texture(include_bytes!("../../assets/models/duck/texture.png"))
     .set_position(Vec3::new(0.5, -0.5, 0.0))
-    .set_scale(Vec3::new(0.006, 0.006, 0.006))
+    .set_scale(Vec3::new(0.6, 0.6, 0.6))
     .set_rotation(-26.0, trs_24::types::RotAxis::Roll);
 
     // Create a 3d map model from its glTF model
     let map = trs_24::renderer::Model::new(
         include_bytes!("../../assets/models/map/scene.gltf"),
         include_bytes!("../../assets/models/map/scene.bin"),
-        include_bytes!("../../assets/models/map/texture.png"),
     )
+    .set_texture(include_bytes!("../../assets/models/map/texture.png"))
     .set_position(Vec3::new(-0.5, 0.0, 0.0))
     .set_scale(Vec3::new(0.08, 0.08, 0.08));
 
     let sword = trs_24::renderer::Model::new(
         include_bytes!("../../assets/models/sword/scene.gltf"),
         include_bytes!("../../assets/models/sword/scene.bin"),
-        include_bytes!("../../assets/models/sword/texture.jpeg"),
     )
+    .set_texture(include_bytes!("../../assets/models/sword/texture.jpeg"))
     .set_position(Vec3::new(-1.6, -0.5, -1.0))
     .set_scale(Vec3::new(0.05, 0.05, 0.05))
     .set_rotation(42.0, RotAxis::Roll);
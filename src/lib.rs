@@ -6,8 +6,10 @@ pub mod ui;
 
 pub mod overture {
     use crate::renderer::Camera;
+    use crate::renderer::Effect;
     use crate::renderer::Model;
     use crate::renderer::Renderer;
+    use crate::renderer::ShaderError;
     use crate::types;
     use crate::ui;
     use glutin::config::{Config, ConfigSurfaceTypes, ConfigTemplate, ConfigTemplateBuilder};
@@ -43,8 +45,11 @@ pub mod overture {
         winsys_display: Option<RawDisplayHandle>,
         glutin_display: Option<Display>,
         surface_state: Option<SurfaceState>,
-        context: Option<glutin::context::PossiblyCurrentContext>,
+        // Dropped before the context so its GL objects are deleted while it's current
         pub render_state: Option<Renderer>,
+        context: Option<glutin::context::PossiblyCurrentContext>,
+        /// Added to the renderer once it exists
+        effects: Vec<Effect>,
     }
 
     impl Scene {
@@ -63,9 +68,18 @@ pub mod overture {
                 surface_state: None,
                 context: None,
                 render_state: None,
+                effects: Vec::new(),
             }
         }
 
+        /// Runs a full-screen effect over every frame, after the ones added before.
+        /// Turn it off later through `render_state`'s `post_process`, by the order
+        /// it was added in.
+        pub fn add_effect(mut self, effect: Effect) -> Self {
+            self.effects.push(effect);
+            self
+        }
+
         #[allow(unused_variables)]
         fn create_display(
             raw_display: RawDisplayHandle,
@@ -199,13 +213,25 @@ pub mod overture {
             self.surface_state = Some(surface_state);
         }
 
-        fn ensure_renderer(&mut self, models: &Vec<Model>, ui: &Vec<ui::Element>) {
+        fn ensure_renderer(
+            &mut self,
+            models: &Vec<Model>,
+            ui: &Vec<ui::Element>,
+        ) -> Result<(), ShaderError> {
             let glutin_display = self
                 .glutin_display
                 .as_ref()
                 .expect("Can't ensure renderer without a Glutin Display connection");
-            self.render_state
-                .get_or_insert_with(|| Renderer::new(glutin_display, models, ui));
+            if self.render_state.is_none() {
+                let mut renderer = Renderer::new(glutin_display, models, ui)?;
+                for effect in self.effects.drain(..) {
+                    if let Err(err) = renderer.add_effect(effect) {
+                        eprintln!("Failed to add a post-process effect: {}", err);
+                    }
+                }
+                self.render_state = Some(renderer);
+            }
+            Ok(())
         }
 
         fn queue_redraw(&self) {
@@ -221,7 +247,12 @@ pub mod overture {
             ui: &Vec<ui::Element>,
         ) {
             self.ensure_surface_and_context(event_loop);
-            self.ensure_renderer(models, ui);
+            // A broken built-in shader would only ever draw a blank window
+            if let Err(err) = self.ensure_renderer(models, ui) {
+                eprintln!("Failed to build the renderer's shaders: {}", err);
+                event_loop.exit();
+                return;
+            }
             self.queue_redraw();
         }
 
@@ -236,13 +267,16 @@ pub mod overture {
 
             #[cfg(debug_assertions)]
             let mut left_mouse_button_pressed = false;
+            // Set by F12, the next frame is saved before it's swapped
+            #[cfg(debug_assertions)]
+            let mut screenshot_requested = false;
 
             let mut key_input_vec: Vec<(Key, ElementState, Box<dyn Fn(&mut Scene)>)> = Vec::new();
             let mut mouse_input_vec: Vec<(MouseButton, ElementState, Box<dyn Fn(&mut Scene)>)> =
                 Vec::new();
             let mut touch_input_vec: Vec<(TouchPhase, Box<dyn Fn(&mut Scene)>)> = Vec::new();
 
-            // let mut previous_frame_time = Instant::now();
+            let mut previous_frame_time = Instant::now();
 
             // let mut imgui = imgui::Context::create();
             // let mut platform = imgui_winit_support::WinitPlatform::init(&mut imgui);
@@ -298,11 +332,11 @@ pub mod overture {
                         }
                         Event::WindowEvent { event, .. } => match event {
                             WindowEvent::RedrawRequested => {
-                                // let current_frame_time = std::time::Instant::now();
-                                // let timestep = current_frame_time
-                                //     .duration_since(previous_frame_time)
-                                //     .as_secs_f32();
-                                // previous_frame_time = current_frame_time;
+                                let current_frame_time = std::time::Instant::now();
+                                let timestep = current_frame_time
+                                    .duration_since(previous_frame_time)
+                                    .as_secs_f32();
+                                previous_frame_time = current_frame_time;
 
                                 // println!("{:?}ms", timestep * 1000.0);
 
@@ -316,8 +350,15 @@ pub mod overture {
 
                                     if let Some(ctx) = &self.context {
                                         if let Some(ref mut renderer) = self.render_state {
+                                            renderer.update(timestep);
                                             renderer.draw(&world_color, &camera);
 
+                                            #[cfg(debug_assertions)]
+                                            if screenshot_requested {
+                                                screenshot_requested = false;
+                                                save_screenshot(renderer);
+                                            }
+
                                             if let Err(err) =
                                                 surface_state.surface.swap_buffers(ctx)
                                             {
@@ -361,6 +402,12 @@ pub mod overture {
                                         KeyCode::ControlLeft => {
                                             camera.position -= camera.speed * camera.up
                                         }
+                                        KeyCode::F12
+                                            if event.state == ElementState::Pressed
+                                                && !event.repeat =>
+                                        {
+                                            screenshot_requested = true;
+                                        }
                                         KeyCode::ShiftLeft => {
                                             camera.speed = if event.state == ElementState::Pressed {
                                                 0.4
@@ -528,6 +575,21 @@ pub mod overture {
         }
     }
 
+    /// Saves the frame as `screenshot-<milliseconds since the epoch>.png` in the
+    /// working directory.
+    #[cfg(debug_assertions)]
+    fn save_screenshot(renderer: &Renderer) {
+        let millis = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |time| time.as_millis());
+        let path = format!("screenshot-{}.png", millis);
+
+        match renderer.capture_frame().save_png(&path) {
+            Ok(()) => println!("Saved {}", path),
+            Err(err) => eprintln!("Failed to save {}: {}", path, err),
+        }
+    }
+
     #[cfg(target_os = "android")]
     pub use winit::platform::android::activity::AndroidApp;
     #[cfg(target_os = "android")]
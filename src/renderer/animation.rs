@@ -0,0 +1,528 @@
+/// How a sampler's keyframes are interpolated, as defined by glTF.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Interpolation {
+    Linear,
+    Step,
+    CubicSpline,
+}
+
+/// The node property an animation channel drives.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ChannelPath {
+    Translation,
+    Rotation,
+    Scale,
+    /// Morph target weights of the node's mesh.
+    Weights,
+}
+
+/// Keyframe times and values. Values are flattened with `components` floats per
+/// element, and cubic spline samplers store an in-tangent, value and out-tangent
+/// per keyframe.
+#[derive(Clone, Debug)]
+pub struct AnimationSampler {
+    pub input: Vec<f32>,
+    pub output: Vec<f32>,
+    pub components: usize,
+    pub interpolation: Interpolation,
+}
+
+impl AnimationSampler {
+    fn value(&self, keyframe: usize) -> &[f32] {
+        let element = match self.interpolation {
+            Interpolation::CubicSpline => keyframe * 3 + 1,
+            Interpolation::Linear | Interpolation::Step => keyframe,
+        };
+        &self.output[element * self.components..(element + 1) * self.components]
+    }
+
+    fn in_tangent(&self, keyframe: usize) -> &[f32] {
+        let element = keyframe * 3;
+        &self.output[element * self.components..(element + 1) * self.components]
+    }
+
+    fn out_tangent(&self, keyframe: usize) -> &[f32] {
+        let element = keyframe * 3 + 2;
+        &self.output[element * self.components..(element + 1) * self.components]
+    }
+
+    /// Samples the value at `time`, clamped to the first and last keyframes.
+    /// Rotations are interpolated spherically and come out normalized.
+    pub fn sample(&self, time: f32, rotation: bool) -> Vec<f32> {
+        let last = self.input.len() - 1;
+        if time <= self.input[0] {
+            return self.value(0).to_vec();
+        }
+        if time >= self.input[last] {
+            return self.value(last).to_vec();
+        }
+
+        // The keyframe at or before `time`, followed by one after it
+        let previous = self.input.partition_point(|t| *t <= time) - 1;
+        let next = previous + 1;
+        let delta = self.input[next] - self.input[previous];
+        let s = (time - self.input[previous]) / delta;
+
+        match self.interpolation {
+            Interpolation::Step => self.value(previous).to_vec(),
+            Interpolation::Linear => mix(self.value(previous), self.value(next), s, rotation),
+            Interpolation::CubicSpline => {
+                let s2 = s * s;
+                let s3 = s2 * s;
+                let p0 = self.value(previous);
+                let m0 = self.out_tangent(previous);
+                let p1 = self.value(next);
+                let m1 = self.in_tangent(next);
+
+                let mut value: Vec<f32> = (0..self.components)
+                    .map(|i| {
+                        (2.0 * s3 - 3.0 * s2 + 1.0) * p0[i]
+                            + (s3 - 2.0 * s2 + s) * delta * m0[i]
+                            + (-2.0 * s3 + 3.0 * s2) * p1[i]
+                            + (s3 - s2) * delta * m1[i]
+                    })
+                    .collect();
+                if rotation {
+                    normalize(&mut value);
+                }
+                value
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct AnimationChannel {
+    pub node: usize,
+    pub path: ChannelPath,
+    pub sampler: AnimationSampler,
+}
+
+#[derive(Clone, Debug)]
+pub struct AnimationClip {
+    pub name: Option<String>,
+    pub channels: Vec<AnimationChannel>,
+    /// Time of the last keyframe across all channels, in seconds.
+    pub duration: f32,
+}
+
+impl AnimationClip {
+    pub fn new(name: Option<String>, channels: Vec<AnimationChannel>) -> AnimationClip {
+        let duration = channels
+            .iter()
+            .filter_map(|channel| channel.sampler.input.last().copied())
+            .fold(0.0, f32::max);
+
+        AnimationClip {
+            name,
+            channels,
+            duration,
+        }
+    }
+}
+
+/// Raised by [`AnimationPlayer`], collected with [`AnimationPlayer::poll_event`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AnimationEvent {
+    /// A clip that doesn't loop reached its end.
+    Finished { clip: usize },
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Playback {
+    clip: usize,
+    time: f32,
+    speed: f32,
+    looping: bool,
+    finished: bool,
+}
+
+impl Playback {
+    /// Moves the playhead, returns true the moment a non-looping clip finishes.
+    fn advance(&mut self, dt: f32, duration: f32) -> bool {
+        self.time += dt * self.speed;
+
+        if self.looping {
+            if duration > 0.0 {
+                self.time = self.time.rem_euclid(duration);
+            }
+            return false;
+        }
+
+        let at_end =
+            (self.speed >= 0.0 && self.time >= duration) || (self.speed < 0.0 && self.time <= 0.0);
+        self.time = self.time.clamp(0.0, duration);
+        if at_end && !self.finished {
+            self.finished = true;
+            return true;
+        }
+        false
+    }
+}
+
+/// Plays back the animations of a model, optionally crossfading from one clip to another.
+pub struct AnimationPlayer {
+    clips: Vec<AnimationClip>,
+    current: Option<Playback>,
+    /// The clip being faded out, with the elapsed and total blend time.
+    fading: Option<(Playback, f32, f32)>,
+    looping: bool,
+    speed: f32,
+    events: Vec<AnimationEvent>,
+}
+
+impl AnimationPlayer {
+    pub fn new(clips: Vec<AnimationClip>) -> AnimationPlayer {
+        AnimationPlayer {
+            clips,
+            current: None,
+            fading: None,
+            looping: true,
+            speed: 1.0,
+            events: Vec::new(),
+        }
+    }
+
+    pub fn clip_count(&self) -> usize {
+        self.clips.len()
+    }
+
+    pub fn clip_index(&self, name: &str) -> Option<usize> {
+        self.clips
+            .iter()
+            .position(|clip| clip.name.as_deref() == Some(name))
+    }
+
+    pub fn clip_name(&self, clip: usize) -> Option<&str> {
+        self.clips.get(clip)?.name.as_deref()
+    }
+
+    pub fn duration(&self, clip: usize) -> Option<f32> {
+        self.clips.get(clip).map(|clip| clip.duration)
+    }
+
+    /// Starts `clip` from the beginning, cutting off whatever was playing.
+    /// Returns false if there's no such clip.
+    pub fn play(&mut self, clip: usize) -> bool {
+        if clip >= self.clips.len() {
+            return false;
+        }
+
+        self.fading = None;
+        self.current = Some(self.start(clip));
+        true
+    }
+
+    pub fn play_by_name(&mut self, name: &str) -> bool {
+        match self.clip_index(name) {
+            Some(clip) => self.play(clip),
+            None => false,
+        }
+    }
+
+    /// Starts `clip` and crossfades into it from the current clip over `seconds`.
+    pub fn blend_to(&mut self, clip: usize, seconds: f32) -> bool {
+        if clip >= self.clips.len() {
+            return false;
+        }
+
+        self.fading = match self.current.take() {
+            Some(current) if seconds > 0.0 => Some((current, 0.0, seconds)),
+            _ => None,
+        };
+        self.current = Some(self.start(clip));
+        true
+    }
+
+    pub fn stop(&mut self) {
+        self.current = None;
+        self.fading = None;
+    }
+
+    /// Looping applies to the current clip and every clip started afterwards.
+    pub fn set_looping(&mut self, looping: bool) {
+        self.looping = looping;
+        if let Some(current) = &mut self.current {
+            current.looping = looping;
+        }
+    }
+
+    /// Playback rate, 1.0 being normal speed. Negative values play backwards.
+    pub fn set_speed(&mut self, speed: f32) {
+        self.speed = speed;
+        if let Some(current) = &mut self.current {
+            current.speed = speed;
+        }
+    }
+
+    pub fn current_clip(&self) -> Option<usize> {
+        self.current.map(|current| current.clip)
+    }
+
+    /// Playhead of the current clip, in seconds.
+    pub fn time(&self) -> Option<f32> {
+        self.current.map(|current| current.time)
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.current.is_some_and(|current| !current.finished)
+    }
+
+    /// Takes the oldest event that hasn't been polled yet.
+    pub fn poll_event(&mut self) -> Option<AnimationEvent> {
+        if self.events.is_empty() {
+            None
+        } else {
+            Some(self.events.remove(0))
+        }
+    }
+
+    fn start(&self, clip: usize) -> Playback {
+        Playback {
+            clip,
+            time: if self.speed < 0.0 {
+                self.clips[clip].duration
+            } else {
+                0.0
+            },
+            speed: self.speed,
+            looping: self.looping,
+            finished: false,
+        }
+    }
+
+    pub fn advance(&mut self, dt: f32) {
+        if let Some(current) = &mut self.current {
+            if current.advance(dt, self.clips[current.clip].duration) {
+                self.events
+                    .push(AnimationEvent::Finished { clip: current.clip });
+            }
+        }
+
+        if let Some((fading, elapsed, total)) = &mut self.fading {
+            fading.advance(dt, self.clips[fading.clip].duration);
+            *elapsed += dt;
+            if *elapsed >= *total {
+                self.fading = None;
+            }
+        }
+    }
+
+    /// Samples every animated node property. Properties only one of two blended
+    /// clips animates are blended against `rest`, the property's unanimated value.
+    pub fn pose(
+        &self,
+        rest: impl Fn(usize, ChannelPath) -> Vec<f32>,
+    ) -> Vec<(usize, ChannelPath, Vec<f32>)> {
+        let Some(current) = self.current else {
+            return Vec::new();
+        };
+        let sample_clip = |playback: &Playback| {
+            self.clips[playback.clip]
+                .channels
+                .iter()
+                .map(|channel| {
+                    let rotation = channel.path == ChannelPath::Rotation;
+                    (
+                        channel.node,
+                        channel.path,
+                        channel.sampler.sample(playback.time, rotation),
+                    )
+                })
+                .collect::<Vec<(usize, ChannelPath, Vec<f32>)>>()
+        };
+
+        let mut pose = sample_clip(&current);
+        let Some((fading, elapsed, total)) = &self.fading else {
+            return pose;
+        };
+        let faded = sample_clip(fading);
+        let weight = (elapsed / total).clamp(0.0, 1.0);
+
+        for (node, path, value) in pose.iter_mut() {
+            let from = faded
+                .iter()
+                .find(|(n, p, _)| n == node && p == path)
+                .map(|(_, _, value)| value.clone())
+                .unwrap_or_else(|| rest(*node, *path));
+            *value = mix(&from, value, weight, *path == ChannelPath::Rotation);
+        }
+        for (node, path, value) in faded {
+            if !pose.iter().any(|(n, p, _)| *n == node && *p == path) {
+                let to = rest(node, path);
+                let blended = mix(&value, &to, weight, path == ChannelPath::Rotation);
+                pose.push((node, path, blended));
+            }
+        }
+
+        pose
+    }
+}
+
+/// Interpolates from `a` to `b`, spherically when they are `[x, y, z, w]` rotations.
+fn mix(a: &[f32], b: &[f32], s: f32, rotation: bool) -> Vec<f32> {
+    if !rotation {
+        return a.iter().zip(b).map(|(a, b)| a + (b - a) * s).collect();
+    }
+
+    // Take the shorter way around
+    let mut dot: f32 = a.iter().zip(b).map(|(a, b)| a * b).sum();
+    let sign = if dot < 0.0 { -1.0 } else { 1.0 };
+    dot *= sign;
+
+    let (weight_a, weight_b) = if dot > 0.9995 {
+        // Nearly identical rotations, a linear blend avoids dividing by ~0
+        (1.0 - s, s)
+    } else {
+        let angle = dot.acos();
+        let sin = angle.sin();
+        (((1.0 - s) * angle).sin() / sin, (s * angle).sin() / sin)
+    };
+
+    let mut value: Vec<f32> = a
+        .iter()
+        .zip(b)
+        .map(|(a, b)| a * weight_a + b * sign * weight_b)
+        .collect();
+    normalize(&mut value);
+    value
+}
+
+fn normalize(values: &mut [f32]) {
+    let length = values.iter().map(|v| v * v).sum::<f32>().sqrt();
+    if length > f32::EPSILON {
+        values.iter_mut().for_each(|v| *v /= length);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: &[f32], expected: &[f32]) {
+        assert_eq!(actual.len(), expected.len());
+        for (a, e) in actual.iter().zip(expected) {
+            assert!((a - e).abs() < 1e-4, "{:?} != {:?}", actual, expected);
+        }
+    }
+
+    fn sampler(
+        interpolation: Interpolation,
+        output: Vec<f32>,
+        components: usize,
+    ) -> AnimationSampler {
+        AnimationSampler {
+            input: vec![0.0, 1.0, 2.0],
+            output,
+            components,
+            interpolation,
+        }
+    }
+
+    fn clip(name: &str, node: usize, values: Vec<f32>) -> AnimationClip {
+        AnimationClip::new(
+            Some(name.to_string()),
+            vec![AnimationChannel {
+                node,
+                path: ChannelPath::Translation,
+                sampler: sampler(Interpolation::Linear, values, 3),
+            }],
+        )
+    }
+
+    #[test]
+    fn interpolates_linearly_and_clamps() {
+        let sampler = sampler(Interpolation::Linear, vec![0.0, 10.0, 30.0], 1);
+
+        assert_close(&sampler.sample(-1.0, false), &[0.0]);
+        assert_close(&sampler.sample(0.25, false), &[2.5]);
+        assert_close(&sampler.sample(1.5, false), &[20.0]);
+        assert_close(&sampler.sample(5.0, false), &[30.0]);
+    }
+
+    #[test]
+    fn holds_values_with_step_interpolation() {
+        let sampler = sampler(Interpolation::Step, vec![0.0, 10.0, 30.0], 1);
+
+        assert_close(&sampler.sample(0.99, false), &[0.0]);
+        assert_close(&sampler.sample(1.0, false), &[10.0]);
+        assert_close(&sampler.sample(1.5, false), &[10.0]);
+    }
+
+    #[test]
+    fn follows_cubic_spline_tangents() {
+        // Keyframes 0, 1, 0 with slopes of 0, 0 and -1 at each
+        let sampler = sampler(
+            Interpolation::CubicSpline,
+            vec![0.0, 0.0, 0.0, 0.0, 1.0, 0.0, -1.0, 0.0, -1.0],
+            1,
+        );
+
+        assert_close(&sampler.sample(0.5, false), &[0.5]);
+        assert_close(&sampler.sample(1.0, false), &[1.0]);
+        // Hermite basis at s = 0.5 with p0 = 1, m0 = 0, p1 = 0, m1 = -1
+        assert_close(&sampler.sample(1.5, false), &[0.5 + 0.125]);
+    }
+
+    #[test]
+    fn slerps_rotations_the_short_way() {
+        let half = std::f32::consts::FRAC_1_SQRT_2;
+        // Identity to 90 degrees around Y, the second stored with a flipped sign
+        let sampler = AnimationSampler {
+            input: vec![0.0, 1.0],
+            output: vec![0.0, 0.0, 0.0, 1.0, 0.0, -half, 0.0, -half],
+            components: 4,
+            interpolation: Interpolation::Linear,
+        };
+
+        let angle = std::f32::consts::FRAC_PI_8;
+        assert_close(
+            &sampler.sample(0.5, true),
+            &[0.0, angle.sin(), 0.0, angle.cos()],
+        );
+    }
+
+    #[test]
+    fn loops_and_reports_finished_clips() {
+        let mut player = AnimationPlayer::new(vec![clip("walk", 0, vec![0.0; 9])]);
+        assert!(player.play_by_name("walk"));
+
+        player.advance(2.5);
+        assert_close(&[player.time().unwrap()], &[0.5]);
+        assert_eq!(player.poll_event(), None);
+
+        player.set_looping(false);
+        player.set_speed(2.0);
+        player.advance(1.0);
+        assert_eq!(player.time(), Some(2.0));
+        assert!(!player.is_playing());
+        assert_eq!(
+            player.poll_event(),
+            Some(AnimationEvent::Finished { clip: 0 })
+        );
+        assert_eq!(player.poll_event(), None);
+    }
+
+    #[test]
+    fn crossfades_between_clips() {
+        let mut player = AnimationPlayer::new(vec![
+            clip("idle", 0, vec![0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0]),
+            clip("run", 1, vec![4.0, 0.0, 0.0, 4.0, 0.0, 0.0, 4.0, 0.0, 0.0]),
+        ]);
+        let rest = |node: usize, _| vec![node as f32 * 10.0, 0.0, 0.0];
+
+        player.play(0);
+        player.blend_to(1, 1.0);
+        player.advance(0.25);
+
+        let pose = player.pose(rest);
+        // The incoming clip blends from node 1's rest pose, the outgoing one towards node 0's
+        let node_0 = pose.iter().find(|(node, _, _)| *node == 0).unwrap();
+        let node_1 = pose.iter().find(|(node, _, _)| *node == 1).unwrap();
+        assert_close(&node_0.2, &[0.0, 0.0, 0.0]);
+        assert_close(&node_1.2, &[8.5, 0.0, 0.0]);
+
+        player.advance(1.0);
+        assert_eq!(player.pose(rest).len(), 1);
+    }
+}
@@ -0,0 +1,162 @@
+use crate::renderer::gl;
+use crate::renderer::mesh;
+use crate::renderer::texture;
+use crate::ui;
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::rc::{Rc, Weak};
+
+/// Identifies the buffers of one glTF primitive, loaded a specific way.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct MeshKey {
+    /// [`content_key`] of the glTF JSON and its buffers.
+    pub model: u64,
+    pub mesh: usize,
+    pub primitive: usize,
+    pub flat_normals: bool,
+    /// Skinned instances carry joints and weights in their vertices.
+    pub skin: Option<usize>,
+}
+
+/// GPU memory held by live assets, in bytes.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct AssetMemory {
+    pub textures: usize,
+    pub meshes: usize,
+    pub fonts: usize,
+}
+
+/// Caches textures, mesh buffers and rasterized fonts so that every user of the
+/// same asset shares one GPU object. The cache only holds weak references, an
+/// asset goes away once the last handle to it is dropped.
+pub struct AssetServer {
+    gl: gl::Gl,
+    textures: HashMap<(u64, texture::Sampler), Weak<texture::Texture>>,
+    white_texture: Weak<texture::Texture>,
+    meshes: HashMap<MeshKey, Weak<mesh::MeshBuffers>>,
+    fonts: HashMap<(u64, u32), Weak<ui::Glyphs>>,
+}
+
+impl AssetServer {
+    pub fn new(gl: gl::Gl) -> Self {
+        Self {
+            gl,
+            textures: HashMap::new(),
+            white_texture: Weak::new(),
+            meshes: HashMap::new(),
+            fonts: HashMap::new(),
+        }
+    }
+
+    /// Uploads a decoded image, or hands out the texture already made from the same bytes.
+    pub fn texture(
+        &mut self,
+        image: &texture::Image,
+        sampler: texture::Sampler,
+    ) -> Rc<texture::Texture> {
+        let key = (image.key, sampler);
+        if let Some(texture) = self.textures.get(&key).and_then(Weak::upgrade) {
+            return texture;
+        }
+
+        let texture = Rc::new(texture::Texture::from_image(
+            self.gl.clone(),
+            image,
+            sampler,
+        ));
+        self.textures.insert(key, Rc::downgrade(&texture));
+
+        texture
+    }
+
+    pub fn white_texture(&mut self) -> Rc<texture::Texture> {
+        if let Some(texture) = self.white_texture.upgrade() {
+            return texture;
+        }
+
+        let texture = Rc::new(texture::Texture::white(self.gl.clone()));
+        self.white_texture = Rc::downgrade(&texture);
+
+        texture
+    }
+
+    pub fn mesh(&self, key: &MeshKey) -> Option<Rc<mesh::MeshBuffers>> {
+        self.meshes.get(key).and_then(Weak::upgrade)
+    }
+
+    pub fn insert_mesh(&mut self, key: MeshKey, buffers: &Rc<mesh::MeshBuffers>) {
+        self.meshes.insert(key, Rc::downgrade(buffers));
+    }
+
+    /// Rasterizes a font at `size` pixels, or hands out the glyphs already made for it.
+    pub fn font(&mut self, font: &[u8], size: u32) -> Result<Rc<ui::Glyphs>, ui::FontError> {
+        let key = (content_key(&[font]), size);
+        if let Some(glyphs) = self.fonts.get(&key).and_then(Weak::upgrade) {
+            return Ok(glyphs);
+        }
+
+        Ok(self.glyphs(&ui::GlyphBitmaps::rasterize(font, size)?))
+    }
+
+    /// Uploads glyphs rasterized ahead of time, or hands out the ones already made
+    /// for the same font and size.
+    pub fn glyphs(&mut self, bitmaps: &ui::GlyphBitmaps) -> Rc<ui::Glyphs> {
+        let key = (bitmaps.font_key, bitmaps.size);
+        if let Some(glyphs) = self.fonts.get(&key).and_then(Weak::upgrade) {
+            return glyphs;
+        }
+
+        let glyphs = Rc::new(ui::Glyphs::upload(self.gl.clone(), bitmaps));
+        self.fonts.insert(key, Rc::downgrade(&glyphs));
+
+        glyphs
+    }
+
+    /// Sums the GPU memory of every asset that is still in use.
+    pub fn memory_usage(&self) -> AssetMemory {
+        let textures = self
+            .textures
+            .values()
+            .chain(std::iter::once(&self.white_texture))
+            .filter_map(Weak::upgrade)
+            .map(|texture| texture.size_in_bytes())
+            .sum();
+        let meshes = self
+            .meshes
+            .values()
+            .filter_map(Weak::upgrade)
+            .map(|buffers| buffers.size_in_bytes())
+            .sum();
+        let fonts = self
+            .fonts
+            .values()
+            .filter_map(Weak::upgrade)
+            .map(|glyphs| glyphs.size_in_bytes())
+            .sum();
+
+        AssetMemory {
+            textures,
+            meshes,
+            fonts,
+        }
+    }
+
+    /// Forgets assets nobody holds a handle to anymore.
+    pub fn prune(&mut self) {
+        self.textures
+            .retain(|_, texture| texture.strong_count() > 0);
+        self.meshes.retain(|_, buffers| buffers.strong_count() > 0);
+        self.fonts.retain(|_, glyphs| glyphs.strong_count() > 0);
+    }
+}
+
+/// Hashes asset bytes into a cache key. Equal keys are treated as equal content.
+pub fn content_key(parts: &[&[u8]]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    for part in parts {
+        part.hash(&mut hasher);
+    }
+    hasher.finish()
+}
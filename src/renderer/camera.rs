@@ -1,4 +1,4 @@
-use crate::renderer::gl;
+use crate::renderer::shader;
 
 pub enum ProjectionType {
     Perspective,
@@ -32,46 +32,38 @@ impl Camera {
 
     pub fn adjust(
         &self,
-        gl: gl::Gl,
-        program: gl::types::GLuint,
+        program: &shader::ShaderProgram,
         projection_type: ProjectionType,
         fov_deg: f32,
         near_plane: f32,
         far_plane: f32,
     ) {
-        unsafe {
-            gl.UseProgram(program);
+        program.bind();
 
-            let view: nalgebra_glm::Mat4 = nalgebra_glm::look_at(
-                &self.position,
-                &(self.position + self.orientation),
-                &self.up,
-            );
+        let view: nalgebra_glm::Mat4 = nalgebra_glm::look_at(
+            &self.position,
+            &(self.position + self.orientation),
+            &self.up,
+        );
 
-            let matrix = match projection_type {
-                ProjectionType::Perspective => {
-                    let projection: nalgebra_glm::Mat4 = nalgebra_glm::perspective_fov(
-                        fov_deg,
-                        self.width,
-                        self.height,
-                        near_plane,
-                        far_plane,
-                    );
-                    projection * view
-                }
-                ProjectionType::Orthographic => {
-                    let projection: nalgebra_glm::Mat4 =
-                        nalgebra_glm::ortho(-1.0, 1.0, -1.0, 1.0, -1.0, 1.0);
-                    projection
-                }
-            };
+        let matrix = match projection_type {
+            ProjectionType::Perspective => {
+                let projection: nalgebra_glm::Mat4 = nalgebra_glm::perspective_fov(
+                    fov_deg,
+                    self.width,
+                    self.height,
+                    near_plane,
+                    far_plane,
+                );
+                projection * view
+            }
+            ProjectionType::Orthographic => {
+                let projection: nalgebra_glm::Mat4 =
+                    nalgebra_glm::ortho(-1.0, 1.0, -1.0, 1.0, -1.0, 1.0);
+                projection
+            }
+        };
 
-            gl.UniformMatrix4fv(
-                gl.GetUniformLocation(program, b"cam_matrix\0".as_ptr() as *const _),
-                1,
-                gl::FALSE,
-                matrix.as_slice().as_ptr(),
-            );
-        }
+        program.set_mat4(program.locations().cam_matrix, &matrix);
     }
 }
@@ -0,0 +1,273 @@
+use crate::renderer::gl;
+
+use std::cell::Cell;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+/// The kinds of GL objects the renderer creates.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GlObjectKind {
+    Buffer,
+    Texture,
+    Program,
+    Framebuffer,
+    Renderbuffer,
+}
+
+/// Number of GL objects with a live handle, per kind.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LiveObjects {
+    pub buffers: usize,
+    pub textures: usize,
+    pub programs: usize,
+    pub framebuffers: usize,
+    pub renderbuffers: usize,
+}
+
+/// Identifies a GL context. Object ids only mean something in the context that
+/// made them, so counts and deferred deletions are kept per context.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ContextId(u64);
+
+impl ContextId {
+    /// A context id no other call returns.
+    pub fn new() -> ContextId {
+        static NEXT: AtomicU64 = AtomicU64::new(0);
+        ContextId(NEXT.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+impl Default for ContextId {
+    fn default() -> Self {
+        ContextId::new()
+    }
+}
+
+/// Live objects of every context that has any.
+static LIVE: Mutex<Vec<(Option<ContextId>, LiveObjects)>> = Mutex::new(Vec::new());
+
+/// Objects dropped while their context wasn't current, deleted by the next
+/// [`flush_deletions`] with it current. Objects made before any context was set
+/// current go to whichever context flushes first.
+static PENDING: Mutex<Vec<(Option<ContextId>, GlObjectKind, gl::types::GLuint)>> =
+    Mutex::new(Vec::new());
+
+thread_local! {
+    static CURRENT: Cell<Option<ContextId>> = const { Cell::new(None) };
+}
+
+/// Owns one GL object and deletes it when dropped. Dropping it while the context it
+/// was made in isn't current on the thread queues the deletion for [`flush_deletions`].
+#[derive(Debug)]
+pub struct GlObject {
+    gl: gl::Gl,
+    context: Option<ContextId>,
+    kind: GlObjectKind,
+    id: gl::types::GLuint,
+}
+
+impl GlObject {
+    pub fn buffer(gl: gl::Gl) -> GlObject {
+        let mut id = 0;
+        unsafe { gl.GenBuffers(1, &mut id) };
+        GlObject::from_raw(gl, GlObjectKind::Buffer, id)
+    }
+
+    pub fn texture(gl: gl::Gl) -> GlObject {
+        let mut id = 0;
+        unsafe { gl.GenTextures(1, &mut id) };
+        GlObject::from_raw(gl, GlObjectKind::Texture, id)
+    }
+
+    pub fn framebuffer(gl: gl::Gl) -> GlObject {
+        let mut id = 0;
+        unsafe { gl.GenFramebuffers(1, &mut id) };
+        GlObject::from_raw(gl, GlObjectKind::Framebuffer, id)
+    }
+
+    pub fn renderbuffer(gl: gl::Gl) -> GlObject {
+        let mut id = 0;
+        unsafe { gl.GenRenderbuffers(1, &mut id) };
+        GlObject::from_raw(gl, GlObjectKind::Renderbuffer, id)
+    }
+
+    /// Takes ownership of a linked program, see [`crate::renderer::ShaderProgram`].
+    pub fn program(gl: gl::Gl, program: gl::types::GLuint) -> GlObject {
+        GlObject::from_raw(gl, GlObjectKind::Program, program)
+    }
+
+    fn from_raw(gl: gl::Gl, kind: GlObjectKind, id: gl::types::GLuint) -> GlObject {
+        let context = current_context();
+        count(context, kind, true);
+        GlObject {
+            gl,
+            context,
+            kind,
+            id,
+        }
+    }
+
+    pub fn id(&self) -> gl::types::GLuint {
+        self.id
+    }
+
+    pub fn kind(&self) -> GlObjectKind {
+        self.kind
+    }
+}
+
+impl Drop for GlObject {
+    fn drop(&mut self) {
+        count(self.context, self.kind, false);
+
+        if self.context.is_some() && current_context() == self.context {
+            unsafe { delete(&self.gl, self.kind, self.id) };
+        } else {
+            lock(&PENDING).push((self.context, self.kind, self.id));
+        }
+    }
+}
+
+/// Tells drops on this thread which context is current, so objects of that context
+/// are deleted right away. [`crate::renderer::Renderer`] sets it when it's made and
+/// at the start of every frame.
+pub fn set_current_context(context: Option<ContextId>) {
+    CURRENT.with(|cell| cell.set(context));
+}
+
+pub fn current_context() -> Option<ContextId> {
+    CURRENT.with(Cell::get)
+}
+
+/// Deletes the objects of the current context whose handles were dropped while it
+/// wasn't current, along with any made while no context was set current. The
+/// renderer calls this at the start of every frame.
+pub fn flush_deletions(gl: &gl::Gl) {
+    let Some(context) = current_context() else {
+        return;
+    };
+    for (kind, id) in take_pending(context) {
+        unsafe { delete(gl, kind, id) };
+    }
+}
+
+/// Counts the GL objects of the current context still owned by a handle, to assert
+/// on in leak tests.
+pub fn live_objects() -> LiveObjects {
+    let context = current_context();
+    lock(&LIVE)
+        .iter()
+        .find(|(other, _)| *other == context)
+        .map(|(_, live)| *live)
+        .unwrap_or_default()
+}
+
+fn take_pending(context: ContextId) -> Vec<(GlObjectKind, gl::types::GLuint)> {
+    let mut pending = lock(&PENDING);
+    let mut taken = Vec::new();
+    pending.retain(|(other, kind, id)| {
+        if other.is_some_and(|other| other != context) {
+            return true;
+        }
+        taken.push((*kind, *id));
+        false
+    });
+
+    taken
+}
+
+fn count(context: Option<ContextId>, kind: GlObjectKind, created: bool) {
+    let mut live = lock(&LIVE);
+    let index = match live.iter().position(|(other, _)| *other == context) {
+        Some(index) => index,
+        None => {
+            live.push((context, LiveObjects::default()));
+            live.len() - 1
+        }
+    };
+
+    let objects = &mut live[index].1;
+    let counter = match kind {
+        GlObjectKind::Buffer => &mut objects.buffers,
+        GlObjectKind::Texture => &mut objects.textures,
+        GlObjectKind::Program => &mut objects.programs,
+        GlObjectKind::Framebuffer => &mut objects.framebuffers,
+        GlObjectKind::Renderbuffer => &mut objects.renderbuffers,
+    };
+    if created {
+        *counter += 1;
+    } else {
+        *counter -= 1;
+    }
+
+    // Contexts that are gone shouldn't pile up
+    if *objects == LiveObjects::default() {
+        live.swap_remove(index);
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+unsafe fn delete(gl: &gl::Gl, kind: GlObjectKind, id: gl::types::GLuint) {
+    match kind {
+        GlObjectKind::Buffer => gl.DeleteBuffers(1, &id),
+        GlObjectKind::Texture => gl.DeleteTextures(1, &id),
+        GlObjectKind::Program => gl.DeleteProgram(id),
+        GlObjectKind::Framebuffer => gl.DeleteFramebuffers(1, &id),
+        GlObjectKind::Renderbuffer => gl.DeleteRenderbuffers(1, &id),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn queues_deletions_for_the_context_that_made_them() {
+        let gl = gl::Gl::load_with(|_| std::ptr::null());
+        let (made_in, other) = (ContextId::new(), ContextId::new());
+
+        set_current_context(Some(made_in));
+        let buffer = GlObject::from_raw(gl.clone(), GlObjectKind::Buffer, 1);
+        let texture = GlObject::from_raw(gl.clone(), GlObjectKind::Texture, 2);
+        assert_eq!(
+            live_objects(),
+            LiveObjects {
+                buffers: 1,
+                textures: 1,
+                ..LiveObjects::default()
+            }
+        );
+
+        set_current_context(Some(other));
+        drop(buffer);
+        drop(texture);
+        // Nothing of this context is pending, nothing gets deleted with the null `gl`
+        flush_deletions(&gl);
+
+        set_current_context(Some(made_in));
+        assert_eq!(live_objects(), LiveObjects::default());
+        assert_eq!(
+            take_pending(made_in),
+            [(GlObjectKind::Buffer, 1), (GlObjectKind::Texture, 2)]
+        );
+        set_current_context(None);
+    }
+
+    #[test]
+    fn hands_objects_made_without_a_context_to_the_next_flush() {
+        let gl = gl::Gl::load_with(|_| std::ptr::null());
+        set_current_context(None);
+        // Headless tests flushing in parallel may take it first, so it gets an id
+        // GL never hands out
+        let buffer = GlObject::from_raw(gl.clone(), GlObjectKind::Buffer, u32::MAX);
+        assert_eq!(live_objects().buffers, 1);
+        drop(buffer);
+        assert_eq!(live_objects(), LiveObjects::default());
+
+        take_pending(ContextId::new());
+        assert!(!lock(&PENDING).iter().any(|(context, ..)| context.is_none()));
+    }
+}
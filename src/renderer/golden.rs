@@ -0,0 +1,287 @@
+use crate::renderer::headless::{Headless, HeadlessError};
+use crate::renderer::model;
+use crate::renderer::texture::Image;
+use crate::types;
+use crate::ui;
+
+use std::path::{Path, PathBuf};
+
+/// Set to anything to have [`assert_golden`] write the rendered images as the new
+/// references instead of comparing against them.
+pub const BLESS_VAR: &str = "TRS_24_BLESS";
+
+/// A scene to render offscreen for a golden-image test.
+pub struct GoldenScene {
+    width: u32,
+    height: u32,
+    models: Vec<model::Model>,
+    ui: Vec<ui::Element>,
+    world_color: types::RGBA,
+    camera: Option<(types::Vec3, types::Vec3)>,
+    frames: usize,
+}
+
+impl GoldenScene {
+    pub fn new(width: u32, height: u32) -> GoldenScene {
+        GoldenScene {
+            width,
+            height,
+            models: Vec::new(),
+            ui: Vec::new(),
+            world_color: types::RGBA::new(0.0, 0.0, 0.0, 1.0),
+            camera: None,
+            frames: 1,
+        }
+    }
+
+    pub fn add_model(mut self, model: model::Model) -> Self {
+        self.models.push(model);
+        self
+    }
+
+    pub fn add_element(mut self, element: ui::Element) -> Self {
+        self.ui.push(element);
+        self
+    }
+
+    pub fn set_world_color(mut self, world_color: types::RGBA) -> Self {
+        self.world_color = world_color;
+        self
+    }
+
+    /// Places the camera, which otherwise sits where [`crate::renderer::Camera::new`] puts it.
+    pub fn set_camera(mut self, position: types::Vec3, orientation: types::Vec3) -> Self {
+        self.camera = Some((position, orientation));
+        self
+    }
+
+    /// Renders this many frames and keeps the last, so animations can advance.
+    pub fn set_frames(mut self, frames: usize) -> Self {
+        self.frames = frames;
+        self
+    }
+
+    pub fn render(&self) -> Result<Image, HeadlessError> {
+        let mut headless = Headless::new(self.width, self.height, &self.models, &self.ui)?;
+        if let Some((position, orientation)) = self.camera {
+            headless.camera.position = nalgebra_glm::vec3(position.x, position.y, position.z);
+            headless.camera.orientation =
+                nalgebra_glm::vec3(orientation.x, orientation.y, orientation.z);
+        }
+
+        Ok(headless.render(self.frames, &self.world_color))
+    }
+}
+
+/// How far an image is from its reference.
+pub struct Comparison {
+    /// Pixels with a channel further off than the tolerance.
+    pub mismatched: usize,
+    /// The largest difference of any channel.
+    pub max_difference: u8,
+    /// The image in gray, with mismatched pixels in red.
+    pub diff: Image,
+}
+
+/// Compares two images pixel by pixel. A pixel matches if none of its channels
+/// differ by more than `tolerance`. Images of different sizes mismatch everywhere.
+pub fn compare(image: &Image, reference: &Image, tolerance: u8) -> Comparison {
+    let same_size = (image.width, image.height) == (reference.width, reference.height);
+    let mut mismatched = 0;
+    let mut max_difference = 0;
+    let mut diff = Vec::with_capacity(image.pixels.len());
+
+    for (index, pixel) in image.pixels.chunks_exact(4).enumerate() {
+        // Pixels of differently sized images don't line up, they never match
+        let difference = reference
+            .pixels
+            .chunks_exact(4)
+            .nth(index)
+            .filter(|_| same_size)
+            .map(|expected| {
+                pixel
+                    .iter()
+                    .zip(expected)
+                    .map(|(a, b)| a.abs_diff(*b))
+                    .max()
+                    .unwrap_or(0)
+            });
+        max_difference = max_difference.max(difference.unwrap_or(u8::MAX));
+
+        match difference {
+            Some(difference) if difference <= tolerance => {
+                let gray = ((pixel[0] as u32 + pixel[1] as u32 + pixel[2] as u32) / 6) as u8;
+                diff.extend_from_slice(&[gray, gray, gray, 255]);
+            }
+            _ => {
+                mismatched += 1;
+                diff.extend_from_slice(&[255, 0, 0, 255]);
+            }
+        }
+    }
+
+    Comparison {
+        mismatched,
+        max_difference,
+        diff: Image {
+            key: 0,
+            width: image.width,
+            height: image.height,
+            pixels: diff,
+        },
+    }
+}
+
+/// Panics unless `image` matches the PNG at `reference` within `tolerance`, see
+/// [`compare`]. On a mismatch the image and the diff are written next to the
+/// reference as `<name>.actual.png` and `<name>.diff.png`. With [`BLESS_VAR`] set,
+/// the image is written as the reference instead. A missing reference fails, a
+/// mistyped path mustn't pass.
+pub fn assert_golden(image: &Image, reference: impl AsRef<Path>, tolerance: u8) {
+    let reference = reference.as_ref();
+    if std::env::var_os(BLESS_VAR).is_some() {
+        if let Some(directory) = reference.parent() {
+            std::fs::create_dir_all(directory).unwrap();
+        }
+        image.save_png(reference).unwrap();
+        println!("Wrote {}", reference.display());
+        return;
+    }
+
+    if !reference.exists() {
+        let actual_path = sibling(reference, "actual");
+        if let Some(directory) = reference.parent() {
+            std::fs::create_dir_all(directory).unwrap();
+        }
+        image.save_png(&actual_path).unwrap();
+        panic!(
+            "{} doesn't exist, rerun with {}=1 to write it, the image is in {}",
+            reference.display(),
+            BLESS_VAR,
+            actual_path.display()
+        );
+    }
+
+    let bytes = std::fs::read(reference)
+        .unwrap_or_else(|err| panic!("can't read {}: {}", reference.display(), err));
+    let expected = Image::decode(&bytes)
+        .unwrap_or_else(|err| panic!("can't decode {}: {}", reference.display(), err));
+
+    let comparison = compare(image, &expected, tolerance);
+    if comparison.mismatched == 0 {
+        return;
+    }
+
+    let actual_path = sibling(reference, "actual");
+    let diff_path = sibling(reference, "diff");
+    image.save_png(&actual_path).unwrap();
+    comparison.diff.save_png(&diff_path).unwrap();
+    panic!(
+        "{} pixels of {}x{} differ from {} by up to {} (tolerance {}), see {} and {}",
+        comparison.mismatched,
+        image.width,
+        image.height,
+        reference.display(),
+        comparison.max_difference,
+        tolerance,
+        actual_path.display(),
+        diff_path.display()
+    );
+}
+
+/// `scene.png` becomes `scene.<suffix>.png`.
+fn sibling(reference: &Path, suffix: &str) -> PathBuf {
+    let stem = reference
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+    reference.with_file_name(format!("{}.{}.png", stem, suffix))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GOLDEN_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/assets/golden");
+
+    fn solid(width: i32, height: i32, rgba: [u8; 4]) -> Image {
+        Image {
+            key: 0,
+            width,
+            height,
+            pixels: rgba.repeat((width * height) as usize),
+        }
+    }
+
+    #[test]
+    fn counts_pixels_outside_the_tolerance() {
+        let reference = solid(4, 4, [100, 100, 100, 255]);
+        let mut image = solid(4, 4, [102, 100, 100, 255]);
+        image.pixels[0] = 200;
+
+        let comparison = compare(&image, &reference, 2);
+        assert_eq!(comparison.mismatched, 1);
+        assert_eq!(comparison.max_difference, 100);
+        assert_eq!(&comparison.diff.pixels[..4], [255, 0, 0, 255]);
+
+        assert_eq!(compare(&image, &solid(2, 8, [0; 4]), 255).mismatched, 16);
+    }
+
+    #[test]
+    fn matches_the_reference_scene() {
+        let duck = model::Model::new(
+            include_bytes!("../../assets/models/duck/scene.gltf"),
+            include_bytes!("../../assets/models/duck/scene.bin"),
+        )
+        .set_texture(include_bytes!("../../assets/models/duck/texture.png"))
+        .set_position(types::Vec3::new(0.0, -0.5, 0.0))
+        .set_scale(types::Vec3::new(0.6, 0.6, 0.6));
+        let panel = ui::Element::new(types::Element::Shape(ui::ShapeBuilder::new(
+            types::Shape::Square,
+        )))
+        .set_color(types::RGBA::new(0.5, 0.0, 1.0, 0.4))
+        .set_position(types::Vec3::new(0.0, -0.74, 0.0))
+        .set_scale(types::Vec3::new(0.7, 0.2, 0.5))
+        .is_hud(true);
+        let text = ui::Element::new(types::Element::Text(ui::TextBuilder::new(
+            "TRS_24".to_string(),
+            include_bytes!("../../assets/fonts/Antonio-Bold.ttf"),
+            40,
+        )))
+        .set_color(types::RGBA::new(1.0, 1.0, 1.0, 1.0))
+        .set_scale(types::Vec3::new(0.004, 0.004, 0.004))
+        .set_position(types::Vec3::new(-0.2, -0.8, 0.0))
+        .is_hud(true);
+
+        let scene = GoldenScene::new(160, 120)
+            .add_model(duck)
+            .add_element(panel)
+            .add_element(text)
+            .set_world_color(types::RGBA::new(0.1, 0.2, 0.3, 1.0));
+        let image = match scene.render() {
+            Ok(image) => image,
+            Err(HeadlessError::Unavailable(reason)) => {
+                eprintln!("Skipping, {}", reason);
+                return;
+            }
+            Err(err) => panic!("{}", err),
+        };
+
+        assert_golden(&image, Path::new(GOLDEN_DIR).join("scene.png"), 2);
+    }
+
+    #[test]
+    fn fails_without_a_reference() {
+        // Blessing writes missing references
+        if std::env::var_os(BLESS_VAR).is_some() {
+            return;
+        }
+
+        let reference = std::env::temp_dir().join("trs_24-golden/missing.png");
+        let result = std::panic::catch_unwind(|| {
+            assert_golden(&solid(2, 2, [0; 4]), &reference, 0);
+        });
+        assert!(result.is_err());
+        assert!(!reference.exists());
+    }
+}
@@ -0,0 +1,295 @@
+use crate::renderer::gl;
+use crate::renderer::gl_object;
+use crate::renderer::model;
+use crate::renderer::shader;
+use crate::renderer::texture;
+use crate::renderer::{Camera, RenderTarget, Renderer};
+use crate::types;
+use crate::ui;
+
+use glutin::api::egl;
+use glutin::config::{Api, ConfigSurfaceTypes, ConfigTemplateBuilder};
+use glutin::context::{ContextApi, ContextAttributesBuilder, Version};
+use glutin::display::GetDisplayExtensions;
+use glutin::prelude::*;
+use glutin::surface::{PbufferSurface, SurfaceAttributesBuilder};
+
+use std::error::Error;
+use std::fmt;
+use std::num::NonZeroU32;
+
+/// Reasons a [`Headless`] renderer can't be set up.
+#[derive(Debug)]
+pub enum HeadlessError {
+    /// No EGL device can make a GLES2 context without a window, e.g. EGL or Mesa
+    /// isn't installed. Tests treat this as a reason to skip.
+    Unavailable(String),
+    /// The built-in shaders don't build.
+    Shader(shader::ShaderError),
+    /// The offscreen framebuffer isn't complete, with the status GL reported.
+    IncompleteFramebuffer(gl::types::GLenum),
+}
+
+impl fmt::Display for HeadlessError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HeadlessError::Unavailable(reason) => {
+                write!(f, "no offscreen GL context available: {}", reason)
+            }
+            HeadlessError::Shader(err) => write!(f, "{}", err),
+            HeadlessError::IncompleteFramebuffer(status) => {
+                write!(f, "offscreen framebuffer is incomplete: {:#x}", status)
+            }
+        }
+    }
+}
+
+impl Error for HeadlessError {}
+
+/// The GL objects of a headless renderer, dropped ahead of the context.
+struct Offscreen {
+    renderer: Renderer,
+    target: RenderTarget,
+}
+
+/// Renders scenes without a window or display, into an offscreen framebuffer of an
+/// EGL context that has no surface, or a pbuffer where surfaceless contexts aren't
+/// supported. Works with Mesa's llvmpipe, so rendering can be tested on machines
+/// without a GPU.
+///
+/// Mesa compiles GLES 2.0 shaders with a newer GLSL ES version unless
+/// `MESA_GLES_VERSION_OVERRIDE=2.0` is set. Cargo sets it for this crate's tests,
+/// CI running test binaries directly should set it too.
+pub struct Headless {
+    width: u32,
+    height: u32,
+    pub camera: Camera,
+    offscreen: Option<Offscreen>,
+    _surface: Option<egl::surface::Surface<PbufferSurface>>,
+    context: egl::context::PossiblyCurrentContext,
+}
+
+impl Headless {
+    /// Makes the context and a `width` by `height` framebuffer, and loads the models
+    /// and UI elements. Unlike in a window, everything is loaded before this returns.
+    pub fn new(
+        width: u32,
+        height: u32,
+        models: &Vec<model::Model>,
+        ui: &Vec<ui::Element>,
+    ) -> Result<Headless, HeadlessError> {
+        let (display, context, surface) = create_context()?;
+        let mut renderer = Renderer::new(&glutin::display::Display::Egl(display), models, ui)
+            .map_err(HeadlessError::Shader)?;
+
+        let target = RenderTarget::new(renderer.gl().clone(), width, height)
+            .map_err(HeadlessError::IncompleteFramebuffer)?;
+        target.bind();
+        renderer.finish_loading();
+        let offscreen = Offscreen { renderer, target };
+
+        Ok(Headless {
+            width,
+            height,
+            camera: Camera::new(width as f32, height as f32),
+            offscreen: Some(offscreen),
+            _surface: surface,
+            context,
+        })
+    }
+
+    pub fn renderer(&mut self) -> &mut Renderer {
+        &mut self.offscreen.as_mut().unwrap().renderer
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Draws `frames` frames, advancing animations by a 60th of a second before
+    /// each, and returns the last one.
+    pub fn render(&mut self, frames: usize, world_color: &types::RGBA) -> texture::Image {
+        let offscreen = self.offscreen.as_mut().unwrap();
+        for _ in 0..frames {
+            offscreen.renderer.update(1.0 / 60.0);
+            offscreen.renderer.draw(world_color, &self.camera);
+        }
+
+        offscreen.renderer.capture_frame()
+    }
+}
+
+impl Drop for Headless {
+    fn drop(&mut self) {
+        // GL objects go while the context is still current, later drops on this
+        // thread mustn't think it is
+        self.offscreen = None;
+        gl_object::set_current_context(None);
+    }
+}
+
+/// A current context with the display it's on, and the pbuffer it's current with
+/// where surfaceless contexts aren't supported.
+type Context = (
+    egl::display::Display,
+    egl::context::PossiblyCurrentContext,
+    Option<egl::surface::Surface<PbufferSurface>>,
+);
+
+/// Makes a GLES2 context current on the first EGL device that has one, trying
+/// Mesa's software renderer first so images come out the same on every machine.
+fn create_context() -> Result<Context, HeadlessError> {
+    let mut devices: Vec<egl::device::Device> = egl::device::Device::query_devices()
+        .map_err(|err| HeadlessError::Unavailable(err.to_string()))?
+        .collect();
+    devices.sort_by_key(|device| !device.extensions().contains("EGL_MESA_device_software"));
+
+    let mut reasons = Vec::new();
+    for device in &devices {
+        match unsafe { create_device_context(device) } {
+            Ok(context) => return Ok(context),
+            Err(err) => reasons.push(err.to_string()),
+        }
+    }
+    if reasons.is_empty() {
+        reasons.push("no EGL devices".to_string());
+    }
+
+    Err(HeadlessError::Unavailable(reasons.join("; ")))
+}
+
+unsafe fn create_device_context(device: &egl::device::Device) -> glutin::error::Result<Context> {
+    let display = egl::display::Display::with_device(device, None)?;
+
+    let template = ConfigTemplateBuilder::new()
+        .with_alpha_size(8)
+        .with_surface_type(ConfigSurfaceTypes::empty())
+        .with_api(Api::GLES2)
+        .build();
+    let config = display
+        .find_configs(template)?
+        .next()
+        .ok_or_else(|| glutin::error::Error::from(glutin::error::ErrorKind::BadConfig))?;
+
+    let attributes = ContextAttributesBuilder::new()
+        .with_context_api(ContextApi::Gles(Some(Version::new(2, 0))))
+        .build(None);
+    let context = display.create_context(&config, &attributes)?;
+
+    // Contexts without a surface need EGL_KHR_surfaceless_context, a tiny pbuffer
+    // does the same job elsewhere
+    if display.extensions().contains("EGL_KHR_surfaceless_context") {
+        let context = context.make_current_surfaceless()?;
+        return Ok((display, context, None));
+    }
+
+    let size = NonZeroU32::new(1).unwrap();
+    let attributes = SurfaceAttributesBuilder::<PbufferSurface>::new().build(size, size);
+    let surface = display.create_pbuffer_surface(&config, &attributes)?;
+    let context = context.make_current(&surface)?;
+
+    Ok((display, context, Some(surface)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_a_scene_without_a_window() {
+        let square = ui::Element::new(types::Element::Shape(ui::ShapeBuilder::new(
+            types::Shape::Square,
+        )))
+        .set_color(types::RGBA::new(0.0, 1.0, 0.0, 1.0))
+        .set_scale(types::Vec3::new(0.5, 0.5, 0.5))
+        .is_hud(true);
+
+        let mut headless = match Headless::new(64, 64, &vec![], &vec![square]) {
+            Ok(headless) => headless,
+            Err(HeadlessError::Unavailable(reason)) => {
+                eprintln!("Skipping, {}", reason);
+                return;
+            }
+            Err(err) => panic!("{}", err),
+        };
+        let image = headless.render(2, &types::RGBA::new(1.0, 0.0, 0.0, 1.0));
+        assert_eq!((image.width, image.height), (64, 64));
+
+        let pixel = |x: usize, y: usize| &image.pixels[(y * 64 + x) * 4..][..4];
+        assert_eq!(pixel(0, 0), [255, 0, 0, 255]);
+        assert_eq!(pixel(32, 32), [0, 255, 0, 255]);
+    }
+
+    #[test]
+    fn frees_the_gl_objects_of_removed_models_and_elements() {
+        let baseline = match Headless::new(16, 16, &vec![], &vec![]) {
+            Ok(_empty) => gl_object::live_objects(),
+            Err(HeadlessError::Unavailable(reason)) => {
+                eprintln!("Skipping, {}", reason);
+                return;
+            }
+            Err(err) => panic!("{}", err),
+        };
+
+        let duck = model::Model::new(
+            include_bytes!("../../assets/models/duck/scene.gltf"),
+            include_bytes!("../../assets/models/duck/scene.bin"),
+        )
+        .set_texture(include_bytes!("../../assets/models/duck/texture.png"));
+        let shape = ui::Element::new(types::Element::Shape(ui::ShapeBuilder::new(
+            types::Shape::Square,
+        )));
+        let text = ui::Element::new(types::Element::Text(ui::TextBuilder::new(
+            "TRS_24".to_string(),
+            include_bytes!("../../assets/fonts/Antonio-Bold.ttf"),
+            24,
+        )));
+        let mut headless =
+            Headless::new(16, 16, &vec![duck.clone(), duck], &vec![shape, text]).unwrap();
+        let loaded = gl_object::live_objects();
+        assert!(loaded.buffers > baseline.buffers);
+        assert!(loaded.textures > baseline.textures);
+
+        // The second duck shares everything with the first
+        headless.renderer().models.remove(1);
+        headless.render(1, &types::RGBA::new(0.0, 0.0, 0.0, 1.0));
+        assert_eq!(gl_object::live_objects(), loaded);
+
+        headless.renderer().models.clear();
+        headless.renderer().ui.clear();
+        headless.render(1, &types::RGBA::new(0.0, 0.0, 0.0, 1.0));
+        assert_eq!(gl_object::live_objects(), baseline);
+    }
+
+    #[test]
+    fn reports_text_with_a_broken_font() {
+        let text = ui::Element::new(types::Element::Text(ui::TextBuilder::new(
+            "TRS_24".to_string(),
+            b"not a font",
+            24,
+        )));
+        let shape = ui::Element::new(types::Element::Shape(ui::ShapeBuilder::new(
+            types::Shape::Square,
+        )));
+
+        let mut headless = match Headless::new(16, 16, &vec![], &vec![text, shape]) {
+            Ok(headless) => headless,
+            Err(HeadlessError::Unavailable(reason)) => {
+                eprintln!("Skipping, {}", reason);
+                return;
+            }
+            Err(err) => panic!("{}", err),
+        };
+        let renderer = headless.renderer();
+        assert!(!renderer.is_loading());
+        assert_eq!(renderer.ui.len(), 1);
+        assert!(matches!(
+            renderer.ui_errors.as_slice(),
+            [(0, ui::FontError::Invalid(_))]
+        ));
+    }
+}
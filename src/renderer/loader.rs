@@ -0,0 +1,120 @@
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// A small pool of threads running loading work that doesn't touch GL, such as
+/// parsing glTF files and decoding images. Results come back through [`Loader::poll`]
+/// tagged with the ticket [`Loader::submit`] handed out.
+pub struct Loader<T> {
+    jobs: mpsc::Sender<Job>,
+    queue: Arc<Mutex<mpsc::Receiver<Job>>>,
+    results_sender: mpsc::Sender<(usize, thread::Result<T>)>,
+    results: mpsc::Receiver<(usize, thread::Result<T>)>,
+    threads: usize,
+    max_threads: usize,
+    submitted: usize,
+    finished: usize,
+}
+
+impl<T: Send + 'static> Loader<T> {
+    pub fn new() -> Self {
+        let (jobs, queue) = mpsc::channel();
+        let (results_sender, results) = mpsc::channel();
+
+        Self {
+            jobs,
+            queue: Arc::new(Mutex::new(queue)),
+            results_sender,
+            results,
+            threads: 0,
+            max_threads: thread::available_parallelism().map_or(2, |threads| threads.get()),
+            submitted: 0,
+            finished: 0,
+        }
+    }
+
+    /// Queues `work` on a loader thread and returns the ticket its result will carry.
+    pub fn submit(&mut self, work: impl FnOnce() -> T + Send + 'static) -> usize {
+        let ticket = self.submitted;
+        self.submitted += 1;
+
+        let results = self.results_sender.clone();
+        let job: Job = Box::new(move || {
+            let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(work));
+            let _ = results.send((ticket, result));
+        });
+        let _ = self.jobs.send(job);
+
+        // Threads are only started while there's work they could pick up
+        if self.threads < self.max_threads && self.threads < self.submitted - self.finished {
+            let queue = self.queue.clone();
+            thread::spawn(move || loop {
+                let job = match queue.lock() {
+                    Ok(queue) => queue.recv(),
+                    Err(_) => return,
+                };
+                match job {
+                    Ok(job) => job(),
+                    Err(_) => return,
+                }
+            });
+            self.threads += 1;
+        }
+
+        ticket
+    }
+
+    /// Hands out a finished result without blocking. Panics of the work are resumed here.
+    pub fn poll(&mut self) -> Option<(usize, T)> {
+        let result = self.results.try_recv().ok()?;
+        Some(self.finish(result))
+    }
+
+    /// Blocks until the next result is in, `None` once every submitted job has finished.
+    pub fn wait(&mut self) -> Option<(usize, T)> {
+        if self.is_idle() {
+            return None;
+        }
+
+        let result = self.results.recv().ok()?;
+        Some(self.finish(result))
+    }
+
+    fn finish(&mut self, (ticket, result): (usize, thread::Result<T>)) -> (usize, T) {
+        self.finished += 1;
+
+        match result {
+            Ok(value) => (ticket, value),
+            Err(panic) => std::panic::resume_unwind(panic),
+        }
+    }
+
+    /// Whether every submitted job has handed out its result.
+    pub fn is_idle(&self) -> bool {
+        self.finished == self.submitted
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hands_back_results_with_their_tickets() {
+        let mut loader = Loader::new();
+        let tickets: Vec<usize> = (0..8).map(|i| loader.submit(move || i * 10)).collect();
+        assert_eq!(tickets, (0..8).collect::<Vec<usize>>());
+
+        let mut results = Vec::new();
+        while let Some(result) = loader.wait() {
+            results.push(result);
+        }
+        results.sort();
+
+        assert_eq!(results, (0..8).map(|i| (i, i * 10)).collect::<Vec<_>>());
+        assert!(loader.is_idle());
+        assert!(loader.poll().is_none());
+    }
+}
@@ -0,0 +1,255 @@
+use crate::renderer::assets;
+use crate::renderer::gl;
+use crate::renderer::shader;
+use crate::renderer::texture;
+
+use std::borrow::Cow;
+use std::rc::Rc;
+
+/// A value for one of the uniforms a [`Material`]'s shaders declare.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Uniform {
+    Float(f32),
+    Vec2(nalgebra_glm::Vec2),
+    Vec3(nalgebra_glm::Vec3),
+    Vec4(nalgebra_glm::Vec4),
+    Mat4(nalgebra_glm::Mat4),
+    /// The bytes of an image file, decoded along with the model.
+    Texture(Cow<'static, [u8]>),
+}
+
+/// Shaders to draw a model with instead of the built-in ones, and values for the
+/// uniforms they declare.
+///
+/// The renderer keeps setting the uniforms it provides: `cam_matrix` and `matrix`
+/// (`mat4`), `skinned` (`bool`), `joint_matrices` (`mat4[24]`), `tex0` (the base
+/// color texture, on unit 0), `flip_v` (`bool`, set for textures stored bottom row
+/// first), `base_color` (`vec4`), `alpha_cutoff` (`float`) and `opaque` (`bool`).
+/// Vertices come in as the attributes `position`, `normal`, `color`, `tex`, `joints`
+/// and `weights`. Textures set here are bound from unit 1 on.
+#[derive(Clone, Debug)]
+pub struct Material {
+    vertex_source: Cow<'static, [u8]>,
+    fragment_source: Cow<'static, [u8]>,
+    uniforms: Vec<(String, Uniform)>,
+}
+
+impl Material {
+    pub fn new(vertex_source: &'static [u8], fragment_source: &'static [u8]) -> Material {
+        Material {
+            vertex_source: Cow::Borrowed(vertex_source),
+            fragment_source: Cow::Borrowed(fragment_source),
+            uniforms: Vec::new(),
+        }
+    }
+
+    /// Same as [`Material::new`], for shaders read at runtime.
+    pub fn from_source(vertex_source: String, fragment_source: String) -> Material {
+        Material {
+            vertex_source: Cow::Owned(vertex_source.into_bytes()),
+            fragment_source: Cow::Owned(fragment_source.into_bytes()),
+            uniforms: Vec::new(),
+        }
+    }
+
+    /// A full-screen pass for [`crate::renderer::Effect::Custom`], drawn with a built-in
+    /// vertex shader. The fragment shader gets the frame so far as `frame`
+    /// (`sampler2D`), the size of one of its pixels as `texel_size` (`vec2`) and the
+    /// coordinates to sample it at as the varying `uv` (`vec2`).
+    pub fn post_process(fragment_source: &'static [u8]) -> Material {
+        Material::new(include_bytes!("./shaders/post-vert.glsl"), fragment_source)
+    }
+
+    /// Sets the uniform called `name`, replacing any value it already has.
+    pub fn set_uniform(mut self, name: &str, value: Uniform) -> Self {
+        match self.uniforms.iter_mut().find(|(other, _)| other == name) {
+            Some((_, other)) => *other = value,
+            None => self.uniforms.push((name.to_string(), value)),
+        }
+        self
+    }
+
+    pub fn uniforms(&self) -> &[(String, Uniform)] {
+        &self.uniforms
+    }
+
+    /// Decodes the images of the texture uniforms. Fails with the name of the
+    /// uniform and the reason.
+    pub fn decode_textures(&self) -> Result<Vec<(String, texture::Image)>, (String, String)> {
+        self.uniforms
+            .iter()
+            .filter_map(|(name, value)| match value {
+                Uniform::Texture(bytes) => Some(
+                    texture::Image::decode(bytes)
+                        .map(|image| (name.clone(), image))
+                        .map_err(|reason| (name.clone(), reason)),
+                ),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+/// A uniform value with its texture uploaded.
+enum Value {
+    Float(f32),
+    Vec2(nalgebra_glm::Vec2),
+    Vec3(nalgebra_glm::Vec3),
+    Vec4(nalgebra_glm::Vec4),
+    Mat4(nalgebra_glm::Mat4),
+    Texture(Rc<texture::Texture>),
+}
+
+/// A [`Material`] with its program linked and its textures uploaded.
+pub struct ReadyMaterial {
+    gl: gl::Gl,
+    program: shader::ShaderProgram,
+    /// Values by name, with where the program reads them.
+    uniforms: Vec<(String, Option<shader::UniformLocation>, Value)>,
+}
+
+impl ReadyMaterial {
+    /// Builds the program and uploads the textures decoded by [`Material::decode_textures`].
+    /// The context has to be current.
+    pub fn new(
+        gl: gl::Gl,
+        material: &Material,
+        images: &[(String, texture::Image)],
+        assets: &mut assets::AssetServer,
+    ) -> Result<ReadyMaterial, shader::ShaderError> {
+        let program = shader::ShaderProgram::new(
+            gl.clone(),
+            &material.vertex_source,
+            &material.fragment_source,
+        )?;
+
+        let mut ready = ReadyMaterial {
+            gl,
+            program,
+            uniforms: Vec::new(),
+        };
+        for (name, value) in &material.uniforms {
+            let value = match value {
+                Uniform::Texture(_) => {
+                    let Some((_, image)) = images.iter().find(|(other, _)| other == name) else {
+                        continue;
+                    };
+                    Value::Texture(assets.texture(image, texture::Sampler::default()))
+                }
+                value => Value::from_uniform(value),
+            };
+            ready.insert(name, value);
+        }
+
+        Ok(ready)
+    }
+
+    pub fn program(&self) -> &shader::ShaderProgram {
+        &self.program
+    }
+
+    /// Changes a uniform from one frame to the next. Textures are decoded and
+    /// uploaded right away, so the context has to be current.
+    pub fn set_uniform(&mut self, name: &str, value: Uniform) -> Result<(), String> {
+        let value = match value {
+            Uniform::Texture(bytes) => {
+                let image = texture::Image::decode(&bytes)?;
+                Value::Texture(Rc::new(texture::Texture::from_image(
+                    self.gl.clone(),
+                    &image,
+                    texture::Sampler::default(),
+                )))
+            }
+            value => Value::from_uniform(&value),
+        };
+        self.insert(name, value);
+
+        Ok(())
+    }
+
+    /// Same as [`ReadyMaterial::set_uniform`], for a texture that's already uploaded.
+    pub fn set_texture(&mut self, name: &str, texture: Rc<texture::Texture>) {
+        self.insert(name, Value::Texture(texture));
+    }
+
+    fn insert(&mut self, name: &str, value: Value) {
+        match self.uniforms.iter_mut().find(|(other, ..)| other == name) {
+            Some((.., other)) => *other = value,
+            None => {
+                let location = self.program.uniform(name);
+                self.uniforms.push((name.to_string(), location, value));
+            }
+        }
+    }
+
+    /// Makes the program current and sets the material's uniforms.
+    pub fn bind(&self) {
+        self.program.bind();
+
+        let mut unit = 1;
+        for (_, location, value) in &self.uniforms {
+            let location = *location;
+            match value {
+                Value::Float(value) => self.program.set_float(location, *value),
+                Value::Vec2(value) => self.program.set_vec2(location, value),
+                Value::Vec3(value) => self.program.set_vec3(location, value),
+                Value::Vec4(value) => self.program.set_vec4(location, value),
+                Value::Mat4(value) => self.program.set_mat4(location, value),
+                Value::Texture(texture) => {
+                    texture.bind_to(unit);
+                    self.program.set_int(location, unit as i32);
+                    unit += 1;
+                }
+            }
+        }
+
+        // Meshes bind their base color texture without picking a unit
+        unsafe { self.gl.ActiveTexture(gl::TEXTURE0) };
+    }
+}
+
+impl Value {
+    fn from_uniform(uniform: &Uniform) -> Value {
+        match uniform {
+            Uniform::Float(value) => Value::Float(*value),
+            Uniform::Vec2(value) => Value::Vec2(*value),
+            Uniform::Vec3(value) => Value::Vec3(*value),
+            Uniform::Vec4(value) => Value::Vec4(*value),
+            Uniform::Mat4(value) => Value::Mat4(*value),
+            Uniform::Texture(_) => unreachable!("textures have to be uploaded"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replaces_uniforms_set_twice() {
+        let material = Material::new(b"", b"")
+            .set_uniform("time", Uniform::Float(1.0))
+            .set_uniform("tint", Uniform::Vec3(nalgebra_glm::vec3(1.0, 0.0, 0.0)))
+            .set_uniform("time", Uniform::Float(2.0));
+
+        assert_eq!(
+            material.uniforms(),
+            &[
+                ("time".to_string(), Uniform::Float(2.0)),
+                (
+                    "tint".to_string(),
+                    Uniform::Vec3(nalgebra_glm::vec3(1.0, 0.0, 0.0))
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_the_uniform_of_an_undecodable_texture() {
+        let material = Material::new(b"", b"")
+            .set_uniform("noise", Uniform::Texture(Cow::Borrowed(b"not an image")));
+
+        let (name, _) = material.decode_textures().unwrap_err();
+        assert_eq!(name, "noise");
+    }
+}
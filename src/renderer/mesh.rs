@@ -1,38 +1,75 @@
 use crate::renderer::gl;
+use crate::renderer::gl_object::GlObject;
+use crate::renderer::shader;
 use crate::renderer::texture;
 
-pub struct Mesh {
+use std::rc::Rc;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AlphaMode {
+    Opaque,
+    Mask,
+    Blend,
+}
+
+/// Surface parameters read from a glTF material's metallic-roughness block.
+#[derive(Clone)]
+pub struct PbrMaterial {
+    pub base_color: nalgebra_glm::Vec4,
+    pub texture: Rc<texture::Texture>,
+    pub alpha_mode: AlphaMode,
+    pub alpha_cutoff: f32,
+    pub double_sided: bool,
+}
+
+/// Floats per vertex: position (3), normal (3), color (4), texture coordinates (2),
+/// joint indices (4) and joint weights (4).
+pub const VERTEX_SIZE: usize = 20;
+
+/// Size of the `joint_matrices` palette in `shader-vert.glsl`. 24 matrices keep the
+/// shader within the 128 uniform vectors every GLES2 implementation provides.
+pub const MAX_JOINTS: usize = 24;
+
+/// Per-vertex offsets of a morph target, blended in by weight.
+pub struct MorphTarget {
+    pub positions: Vec<nalgebra_glm::Vec3>,
+    pub normals: Vec<nalgebra_glm::Vec3>,
+}
+
+/// Vertex and index buffers of a primitive. Meshes without morph targets can
+/// share them through the asset server.
+pub struct MeshBuffers {
     gl: gl::Gl,
-    indices: Vec<u32>,
-    texture: texture::Texture,
-    vbo: gl::types::GLuint,
-    ebo: gl::types::GLuint,
+    vbo: GlObject,
+    ebo: GlObject,
+    mode: gl::types::GLenum,
+    index_count: usize,
+    vertex_count: usize,
 }
 
-impl Mesh {
+impl MeshBuffers {
     pub fn new(
         gl: gl::Gl,
-        vertices: Vec<f32>,
-        indices: Vec<u32>,
-        texture: texture::Texture,
+        vertices: &[f32],
+        indices: &[u32],
+        mode: gl::types::GLenum,
+        usage: gl::types::GLenum,
     ) -> Self {
         unsafe {
-            let mut vbo = std::mem::zeroed();
-            gl.GenBuffers(1, &mut vbo);
-            gl.BindBuffer(gl::ARRAY_BUFFER, vbo);
+            let vbo = GlObject::buffer(gl.clone());
+            gl.BindBuffer(gl::ARRAY_BUFFER, vbo.id());
             gl.BufferData(
                 gl::ARRAY_BUFFER,
-                (vertices.len() * std::mem::size_of::<f32>()) as gl::types::GLsizeiptr,
+                std::mem::size_of_val(vertices) as gl::types::GLsizeiptr,
                 vertices.as_ptr() as *const _,
-                gl::STATIC_DRAW,
+                usage,
             );
 
-            let mut ebo = std::mem::zeroed();
-            gl.GenBuffers(1, &mut ebo);
-            gl.BindBuffer(gl::ELEMENT_ARRAY_BUFFER, ebo);
+            let ebo = GlObject::buffer(gl.clone());
+            gl.BindBuffer(gl::ELEMENT_ARRAY_BUFFER, ebo.id());
             gl.BufferData(
                 gl::ELEMENT_ARRAY_BUFFER,
-                (indices.len() * std::mem::size_of::<u32>()) as gl::types::GLsizeiptr,
+                std::mem::size_of_val(indices) as gl::types::GLsizeiptr,
                 indices.as_ptr() as *const _,
                 gl::STATIC_DRAW,
             );
@@ -42,112 +79,202 @@ impl Mesh {
 
             Self {
                 gl,
-                indices,
-                texture,
-                ebo,
                 vbo,
+                ebo,
+                mode,
+                index_count: indices.len(),
+                vertex_count: vertices.len() / VERTEX_SIZE,
+            }
+        }
+    }
+
+    /// GPU memory taken by the vertex and index buffers.
+    pub fn size_in_bytes(&self) -> usize {
+        self.vertex_count * VERTEX_SIZE * std::mem::size_of::<f32>()
+            + self.index_count * std::mem::size_of::<u32>()
+    }
+}
+
+pub struct Mesh {
+    gl: gl::Gl,
+    buffers: Rc<MeshBuffers>,
+    /// Unmorphed vertices, only kept around for meshes with morph targets.
+    vertices: Vec<f32>,
+    targets: Vec<MorphTarget>,
+    material: PbrMaterial,
+    skinned: bool,
+}
+
+impl Mesh {
+    pub fn new(
+        gl: gl::Gl,
+        vertices: Vec<f32>,
+        indices: Vec<u32>,
+        mode: gl::types::GLenum,
+        material: PbrMaterial,
+        skinned: bool,
+        targets: Vec<MorphTarget>,
+    ) -> Self {
+        // Morphed meshes rewrite their vertices whenever the weights change
+        let usage = if targets.is_empty() {
+            gl::STATIC_DRAW
+        } else {
+            gl::DYNAMIC_DRAW
+        };
+        let buffers = Rc::new(MeshBuffers::new(
+            gl.clone(),
+            &vertices,
+            &indices,
+            mode,
+            usage,
+        ));
+
+        Self {
+            gl,
+            buffers,
+            vertices: if targets.is_empty() {
+                Vec::new()
+            } else {
+                vertices
+            },
+            targets,
+            material,
+            skinned,
+        }
+    }
+
+    /// Draws buffers that were already uploaded for another mesh.
+    pub fn shared(
+        gl: gl::Gl,
+        buffers: Rc<MeshBuffers>,
+        material: PbrMaterial,
+        skinned: bool,
+    ) -> Self {
+        Self {
+            gl,
+            buffers,
+            vertices: Vec::new(),
+            targets: Vec::new(),
+            material,
+            skinned,
+        }
+    }
+
+    pub fn buffers(&self) -> &Rc<MeshBuffers> {
+        &self.buffers
+    }
+
+    /// Replaces the base color texture, e.g. with the one of a render target.
+    pub fn set_texture(&mut self, texture: Rc<texture::Texture>) {
+        self.material.texture = texture;
+    }
+
+    pub fn morph_target_count(&self) -> usize {
+        self.targets.len()
+    }
+
+    /// Blends the morph targets into the vertex buffer, one weight per target.
+    pub fn set_morph_weights(&mut self, weights: &[f32]) {
+        if self.targets.is_empty() {
+            return;
+        }
+
+        let mut vertices = self.vertices.clone();
+        for (target, weight) in self.targets.iter().zip(weights) {
+            if *weight == 0.0 {
+                continue;
+            }
+            for (i, vertex) in vertices.chunks_exact_mut(VERTEX_SIZE).enumerate() {
+                for axis in 0..3 {
+                    vertex[axis] += weight * target.positions[i][axis];
+                    vertex[3 + axis] += weight * target.normals[i][axis];
+                }
+            }
+        }
+
+        for vertex in vertices.chunks_exact_mut(VERTEX_SIZE) {
+            let normal = nalgebra_glm::make_vec3(&vertex[3..6]);
+            if normal.norm() > f32::EPSILON {
+                vertex[3..6].copy_from_slice(normal.normalize().as_slice());
             }
         }
+
+        unsafe {
+            self.gl.BindBuffer(gl::ARRAY_BUFFER, self.buffers.vbo.id());
+            self.gl.BufferSubData(
+                gl::ARRAY_BUFFER,
+                0,
+                (vertices.len() * std::mem::size_of::<f32>()) as gl::types::GLsizeiptr,
+                vertices.as_ptr() as *const _,
+            );
+            self.gl.BindBuffer(gl::ARRAY_BUFFER, 0);
+        }
     }
+
+    /// Draws the mesh with `model_matrix`. Skinned meshes are additionally
+    /// deformed by `joint_matrices`, one per joint of their skin.
     pub fn draw(
         &self,
-        program: gl::types::GLuint,
-        position: nalgebra_glm::Vec3,
-        scale: nalgebra_glm::Vec3,
-        rotation: nalgebra_glm::Quat,
+        program: &shader::ShaderProgram,
+        model_matrix: &nalgebra_glm::Mat4,
+        joint_matrices: &[nalgebra_glm::Mat4],
     ) {
         unsafe {
-            self.gl.UseProgram(program);
+            program.bind();
+            let locations = program.locations();
 
-            self.gl.BindBuffer(gl::ARRAY_BUFFER, self.vbo);
-            self.gl.BindBuffer(gl::ELEMENT_ARRAY_BUFFER, self.ebo);
+            self.gl.BindBuffer(gl::ARRAY_BUFFER, self.buffers.vbo.id());
+            self.gl
+                .BindBuffer(gl::ELEMENT_ARRAY_BUFFER, self.buffers.ebo.id());
 
-            let translation_matrix =
-                nalgebra_glm::translate(&nalgebra_glm::Mat4::identity(), &position);
-            let rotation_matrix = nalgebra_glm::quat_to_mat4(&rotation);
-            let scale_matrix = nalgebra_glm::scale(&nalgebra_glm::Mat4::identity(), &scale);
+            program.set_mat4(locations.matrix, model_matrix);
 
-            let model_matrix = translation_matrix * rotation_matrix * scale_matrix;
+            program.set_attribute(locations.position, 3, VERTEX_SIZE, 0);
+            program.set_attribute(locations.normal, 3, VERTEX_SIZE, 3);
+            program.set_attribute(locations.color, 4, VERTEX_SIZE, 6);
+            program.set_attribute(locations.tex, 2, VERTEX_SIZE, 10);
+            program.set_attribute(locations.joints, 4, VERTEX_SIZE, 12);
+            program.set_attribute(locations.weights, 4, VERTEX_SIZE, 16);
 
-            self.gl.UniformMatrix4fv(
-                self.gl
-                    .GetUniformLocation(program, b"matrix\0".as_ptr() as *const _),
-                1,
-                gl::FALSE,
-                model_matrix.as_slice().as_ptr() as *const f32,
-            );
+            let skinned = self.skinned && !joint_matrices.is_empty();
+            program.set_bool(locations.skinned, skinned);
+            if skinned {
+                let palette = &joint_matrices[..joint_matrices.len().min(MAX_JOINTS)];
+                program.set_mat4_array(locations.joint_matrices, palette);
+            }
 
-            let pos_attrib = self
-                .gl
-                .GetAttribLocation(program, b"position\0".as_ptr() as *const _);
-            self.gl.VertexAttribPointer(
-                pos_attrib as gl::types::GLuint,
-                3,
-                gl::FLOAT,
-                gl::FALSE,
-                11 * std::mem::size_of::<f32>() as gl::types::GLsizei,
-                std::ptr::null(),
-            );
-            self.gl
-                .EnableVertexAttribArray(pos_attrib as gl::types::GLuint);
-
-            let normal_attrib = self
-                .gl
-                .GetAttribLocation(program, b"normal\0".as_ptr() as *const _);
-            self.gl.VertexAttribPointer(
-                normal_attrib as gl::types::GLuint,
-                3,
-                gl::FLOAT,
-                gl::FALSE,
-                11 * std::mem::size_of::<f32>() as gl::types::GLsizei,
-                (3 * std::mem::size_of::<f32>()) as *const () as *const _,
-            );
-            self.gl
-                .EnableVertexAttribArray(normal_attrib as gl::types::GLuint);
-
-            let color_attrib = self
-                .gl
-                .GetAttribLocation(program, b"color\0".as_ptr() as *const _);
-            self.gl.VertexAttribPointer(
-                color_attrib as gl::types::GLuint,
-                3,
-                gl::FLOAT,
-                gl::FALSE,
-                11 * std::mem::size_of::<f32>() as gl::types::GLsizei,
-                (6 * std::mem::size_of::<f32>()) as *const () as *const _,
-            );
-            self.gl
-                .EnableVertexAttribArray(color_attrib as gl::types::GLuint);
-
-            let tex_attrib = self
-                .gl
-                .GetAttribLocation(program, b"tex\0".as_ptr() as *const _);
-            self.gl.VertexAttribPointer(
-                tex_attrib as gl::types::GLuint,
-                2,
-                gl::FLOAT,
-                gl::FALSE,
-                11 * std::mem::size_of::<f32>() as gl::types::GLsizei,
-                (9 * std::mem::size_of::<f32>()) as *const () as *const _,
-            );
-            self.gl
-                .EnableVertexAttribArray(tex_attrib as gl::types::GLuint);
+            program.set_int(locations.tex0, 0);
+            program.set_bool(locations.flip_v, self.material.texture.is_bottom_up());
+            self.material.texture.bind();
 
-            self.gl.Uniform1i(
-                self.gl
-                    .GetUniformLocation(program, b"tex0\0".as_ptr() as *const _),
-                0,
+            program.set_vec4(locations.base_color, &self.material.base_color);
+
+            // Opaque surfaces ignore alpha entirely, masked ones discard below the cutoff
+            let alpha_cutoff = match self.material.alpha_mode {
+                AlphaMode::Mask => self.material.alpha_cutoff,
+                AlphaMode::Opaque | AlphaMode::Blend => -1.0,
+            };
+            program.set_float(locations.alpha_cutoff, alpha_cutoff);
+            program.set_bool(
+                locations.opaque,
+                self.material.alpha_mode != AlphaMode::Blend,
             );
-            self.texture.bind();
+
+            if self.material.double_sided {
+                self.gl.Disable(gl::CULL_FACE);
+            } else {
+                self.gl.Enable(gl::CULL_FACE);
+                self.gl.CullFace(gl::BACK);
+            }
 
             self.gl.DrawElements(
-                gl::TRIANGLES,
-                self.indices.len() as i32,
+                self.buffers.mode,
+                self.buffers.index_count as i32,
                 gl::UNSIGNED_INT,
                 std::ptr::null(),
             );
 
-            self.texture.unbind();
+            self.material.texture.unbind();
         }
     }
 }
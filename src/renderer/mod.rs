@@ -1,13 +1,46 @@
+mod animation;
+mod assets;
 mod camera;
+mod gl_object;
+#[cfg(egl_backend)]
+mod golden;
+#[cfg(egl_backend)]
+mod headless;
+mod loader;
+mod material;
 mod mesh;
 mod model;
+mod png;
+mod post_process;
+mod render_target;
 mod renderer;
+mod resolver;
 mod shader;
 mod texture;
+#[cfg(debug_assertions)]
+mod watcher;
 
+pub use animation::{AnimationEvent, AnimationPlayer};
+pub use assets::{content_key, AssetMemory, AssetServer};
 pub use camera::Camera;
-pub use model::Model;
+pub use gl_object::{
+    current_context, flush_deletions, live_objects, set_current_context, ContextId, GlObject,
+    GlObjectKind, LiveObjects,
+};
+#[cfg(egl_backend)]
+pub use golden::{assert_golden, compare, Comparison, GoldenScene, BLESS_VAR};
+#[cfg(egl_backend)]
+pub use headless::{Headless, HeadlessError};
+pub use material::{Material, ReadyMaterial, Uniform};
+pub use model::{Model, ModelData, ModelError};
+pub use post_process::{Effect, PostProcess, PostProcessError};
+pub use render_target::RenderTarget;
 pub use renderer::Renderer;
+pub use resolver::{DirectoryResolver, MemoryResolver, Resolver};
+pub use shader::{
+    AttributeLocation, Locations, ShaderError, ShaderProgram, ShaderStage, UniformLocation,
+};
+pub use texture::{Image, Texture};
 
 pub mod gl {
     #![allow(clippy::all)]
@@ -1,342 +1,2178 @@
+use crate::renderer::animation;
+use crate::renderer::assets;
 use crate::renderer::gl;
+use crate::renderer::material;
 use crate::renderer::mesh;
+use crate::renderer::resolver;
+use crate::renderer::shader;
 use crate::renderer::texture;
 use crate::types;
 
 use json::JsonValue;
 
+use std::borrow::Cow;
+use std::collections::HashMap;
 use std::error::Error;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
 
-struct TraverseState {
-    translations_meshes: Vec<nalgebra_glm::Vec3>,
-    rotations_meshes: Vec<nalgebra_glm::Quat>,
-    scales_meshes: Vec<nalgebra_glm::Vec3>,
-    meshes_to_load: Vec<usize>,
+/// A node of the glTF scene graph. Nodes are kept after loading so that joints
+/// can be posed and world matrices recomputed.
+struct Node {
+    name: Option<String>,
+    children: Vec<usize>,
+    /// The node's `matrix`, dropped once the node is posed with TRS values.
+    matrix: Option<nalgebra_glm::Mat4>,
+    translation: nalgebra_glm::Vec3,
+    rotation: nalgebra_glm::Quat,
+    scale: nalgebra_glm::Vec3,
+    rest_matrix: Option<nalgebra_glm::Mat4>,
+    rest_trs: (nalgebra_glm::Vec3, nalgebra_glm::Quat, nalgebra_glm::Vec3),
+    /// Morph target weights of the node's mesh.
+    weights: Vec<f32>,
+    rest_weights: Vec<f32>,
+    mesh: Option<usize>,
+    skin: Option<usize>,
+}
+
+impl Node {
+    fn local_matrix(&self) -> nalgebra_glm::Mat4 {
+        let trans = nalgebra_glm::translate(&nalgebra_glm::Mat4::identity(), &self.translation);
+        let rot = nalgebra_glm::quat_cast(&self.rotation);
+        let sca = nalgebra_glm::scale(&nalgebra_glm::Mat4::identity(), &self.scale);
+
+        self.matrix.unwrap_or_else(nalgebra_glm::Mat4::identity) * trans * rot * sca
+    }
+}
+
+/// A glTF skin: the joint nodes that deform a mesh and their inverse bind matrices.
+struct Skin {
+    joints: Vec<usize>,
+    inverse_bind_matrices: Vec<nalgebra_glm::Mat4>,
+}
+
+/// Reasons a glTF model can fail to load.
+#[derive(Debug)]
+pub enum ModelError {
+    /// The glTF JSON can't be parsed, or holds a value outside of what the spec allows.
+    InvalidJson(String),
+    /// The binary glTF container is malformed.
+    InvalidGlb(String),
+    /// A required property is absent or has the wrong type, e.g. `accessors[2].count`.
+    MissingField(String),
+    /// A primitive lacks a vertex attribute the renderer needs.
+    MissingAttribute {
+        mesh: usize,
+        primitive: usize,
+        attribute: String,
+    },
+    /// An accessor reads past the end of its data, or holds fewer elements than it has to.
+    AccessorOutOfBounds { accessor: usize },
+    /// An accessor uses a component type that isn't valid for what it's used for.
+    UnsupportedComponentType {
+        accessor: usize,
+        component_type: u32,
+    },
+    /// An image can't be decoded. `image` is `None` for the texture override.
+    BadImage {
+        image: Option<usize>,
+        reason: String,
+    },
+    /// A model file can't be read from disk.
+    Io { path: String, reason: String },
+    /// A buffer or image URI can't be fetched.
+    UnresolvedUri { uri: String, reason: String },
+    /// A skin has more joints than the shader's joint palette can hold.
+    TooManyJoints { skin: usize, joints: usize },
+    /// A texture uniform of the model's material can't be decoded.
+    BadMaterialTexture { uniform: String, reason: String },
+    /// The shaders of the model's material don't build.
+    Shader(shader::ShaderError),
+}
+
+impl fmt::Display for ModelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ModelError::InvalidJson(reason) => write!(f, "invalid glTF JSON: {}", reason),
+            ModelError::InvalidGlb(reason) => write!(f, "invalid GLB container: {}", reason),
+            ModelError::MissingField(path) => write!(f, "missing or mistyped field {}", path),
+            ModelError::MissingAttribute {
+                mesh,
+                primitive,
+                attribute,
+            } => write!(
+                f,
+                "meshes[{}].primitives[{}] has no {} attribute",
+                mesh, primitive, attribute
+            ),
+            ModelError::AccessorOutOfBounds { accessor } => {
+                write!(f, "accessors[{}] is out of bounds", accessor)
+            }
+            ModelError::UnsupportedComponentType {
+                accessor,
+                component_type,
+            } => write!(
+                f,
+                "accessors[{}] has unsupported componentType {}",
+                accessor, component_type
+            ),
+            ModelError::BadImage {
+                image: Some(image),
+                reason,
+            } => write!(f, "images[{}] can't be decoded: {}", image, reason),
+            ModelError::BadImage {
+                image: None,
+                reason,
+            } => write!(f, "texture override can't be decoded: {}", reason),
+            ModelError::Io { path, reason } => write!(f, "can't read {}: {}", path, reason),
+            ModelError::UnresolvedUri { uri, reason } => {
+                // Data URIs can be megabytes long
+                let uri: String = uri.chars().take(64).collect();
+                write!(f, "can't resolve {}: {}", uri, reason)
+            }
+            ModelError::TooManyJoints { skin, joints } => write!(
+                f,
+                "skins[{}] has {} joints, at most {} are supported",
+                skin,
+                joints,
+                mesh::MAX_JOINTS
+            ),
+            ModelError::BadMaterialTexture { uniform, reason } => write!(
+                f,
+                "material texture {} can't be decoded: {}",
+                uniform, reason
+            ),
+            ModelError::Shader(err) => write!(f, "material shaders don't build: {}", err),
+        }
+    }
+}
+
+impl Error for ModelError {}
+
+#[derive(Clone)]
+pub struct Model {
+    pub gltf_file: Cow<'static, [u8]>,
+    pub bin_file: Cow<'static, [u8]>,
+    pub texture_file: Option<Cow<'static, [u8]>>,
+    pub normal_mode: types::NormalMode,
+    pub resolver: Option<Arc<dyn resolver::Resolver>>,
+    pub position: types::Vec3,
+    pub scale: types::Vec3,
+    pub rotation: (f32, types::RotAxis),
+    /// File the model was read from, see [`Model::from_path`].
+    pub path: Option<PathBuf>,
+    /// File the override texture was read from, see [`Model::set_texture_path`].
+    pub texture_path: Option<PathBuf>,
+    /// Shaders to draw the model with, see [`Model::set_material`].
+    pub material: Option<material::Material>,
 }
 
-pub struct Model {
-    pub gltf_file: &'static [u8],
-    pub bin_file: &'static [u8],
-    pub texture_file: &'static [u8],
-    pub position: types::Vec3,
-    pub scale: types::Vec3,
-    pub rotation: (f32, types::RotAxis),
-}
+impl Model {
+    pub fn new(gltf_file: &'static [u8], bin_file: &'static [u8]) -> Model {
+        Model::from_cow(Cow::Borrowed(gltf_file), Cow::Borrowed(bin_file))
+    }
+
+    pub fn from_glb(glb_file: &'static [u8]) -> Result<Model, ModelError> {
+        let (gltf_file, bin_file) = parse_glb(glb_file)?;
+
+        Ok(Model::new(gltf_file, bin_file))
+    }
+
+    /// Loads a model from the bytes of a `.glb` file, or of a `.gltf` file whose
+    /// buffers are embedded or fetched through [`Model::set_resolver`].
+    pub fn from_bytes(bytes: Vec<u8>) -> Result<Model, ModelError> {
+        if read_u32_le(&bytes, 0) != Some(GLB_MAGIC) {
+            return Ok(Model::from_cow(Cow::Owned(bytes), Cow::Borrowed(&[])));
+        }
+
+        let (gltf_file, bin_file) = parse_glb(&bytes)?;
+
+        Ok(Model::from_cow(
+            Cow::Owned(gltf_file.to_vec()),
+            Cow::Owned(bin_file.to_vec()),
+        ))
+    }
+
+    /// Reads a `.gltf` or `.glb` file at runtime. Buffers and images it references
+    /// are looked up relative to the file's directory.
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Model, ModelError> {
+        let path = path.as_ref();
+        let bytes = std::fs::read(path).map_err(|err| ModelError::Io {
+            path: path.display().to_string(),
+            reason: err.to_string(),
+        })?;
+        let directory = path.parent().unwrap_or(Path::new("")).to_path_buf();
+
+        let mut model =
+            Model::from_bytes(bytes)?.set_resolver(resolver::DirectoryResolver::new(directory));
+        model.path = Some(path.to_path_buf());
+
+        Ok(model)
+    }
+
+    fn from_cow(gltf_file: Cow<'static, [u8]>, bin_file: Cow<'static, [u8]>) -> Model {
+        Model {
+            gltf_file,
+            bin_file,
+            texture_file: None,
+            normal_mode: types::NormalMode::Smooth,
+            resolver: None,
+            position: types::Vec3::new(0.0, 0.0, 0.0),
+            scale: types::Vec3::new(0.01, 0.01, 0.01),
+            rotation: (0.0, types::RotAxis::Pitch),
+            path: None,
+            texture_path: None,
+            material: None,
+        }
+    }
+
+    /// Uses the given image for every material instead of the textures referenced by the glTF.
+    pub fn set_texture(mut self, texture_file: &'static [u8]) -> Self {
+        self.texture_file = Some(Cow::Borrowed(texture_file));
+        self
+    }
+
+    /// Same as [`Model::set_texture`], for images loaded at runtime.
+    pub fn set_texture_bytes(mut self, texture_file: Vec<u8>) -> Self {
+        self.texture_file = Some(Cow::Owned(texture_file));
+        self
+    }
+
+    /// Same as [`Model::set_texture`], reading the image from a file.
+    pub fn set_texture_path(mut self, path: impl AsRef<Path>) -> Result<Self, ModelError> {
+        let path = path.as_ref();
+        let texture_file = std::fs::read(path).map_err(|err| ModelError::Io {
+            path: path.display().to_string(),
+            reason: err.to_string(),
+        })?;
+        self.texture_file = Some(Cow::Owned(texture_file));
+        self.texture_path = Some(path.to_path_buf());
+
+        Ok(self)
+    }
+
+    /// Picks how normals are generated for primitives that come without a `NORMAL` attribute.
+    pub fn set_normal_mode(mut self, normal_mode: types::NormalMode) -> Self {
+        self.normal_mode = normal_mode;
+        self
+    }
+
+    /// Fetches buffers and images the glTF references by URI. Without a resolver
+    /// only the bin file and embedded data URIs are available, and external images
+    /// are replaced with plain white.
+    pub fn set_resolver(mut self, resolver: impl resolver::Resolver + 'static) -> Self {
+        self.resolver = Some(Arc::new(resolver));
+        self
+    }
+
+    pub fn set_position(mut self, position: types::Vec3) -> Self {
+        self.position = position;
+        self
+    }
+
+    pub fn set_scale(mut self, scale: types::Vec3) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    pub fn set_rotation(mut self, rotation: f32, axis: types::RotAxis) -> Self {
+        self.rotation = (rotation, axis);
+        self
+    }
+
+    /// Draws the model with the material's shaders instead of the built-in ones.
+    pub fn set_material(mut self, material: material::Material) -> Self {
+        self.material = Some(material);
+        self
+    }
+
+    /// Parses the glTF and decodes its images, everything short of uploading to the
+    /// GPU. Needs no GL context, the renderer calls it on its loader threads.
+    pub fn load(&self) -> Result<ModelData, ModelError> {
+        let mut data = ModelData::load(
+            &self.gltf_file,
+            &self.bin_file,
+            self.texture_file.as_deref(),
+            self.normal_mode,
+            self.resolver.clone(),
+        )?;
+
+        data.position = nalgebra_glm::vec3(self.position.x, self.position.y, self.position.z);
+        data.scale = nalgebra_glm::vec3(self.scale.x, self.scale.y, self.scale.z);
+        let axis = match self.rotation.1 {
+            types::RotAxis::Pitch => nalgebra_glm::vec3(1.0, 0.0, 0.0),
+            types::RotAxis::Roll => nalgebra_glm::vec3(0.0, 1.0, 0.0),
+            types::RotAxis::Yaw => nalgebra_glm::vec3(0.0, 0.0, 1.0),
+        };
+        data.rotation = nalgebra_glm::quat_angle_axis(self.rotation.0, &axis);
+
+        if let Some(material) = &self.material {
+            data.material_images = material
+                .decode_textures()
+                .map_err(|(uniform, reason)| ModelError::BadMaterialTexture { uniform, reason })?;
+            data.material = Some(material.clone());
+        }
+
+        // Models read from files can be read again once those change
+        if let Some(path) = &self.path {
+            data.watched_files = self.watched_files(path, &data.json);
+            data.source = Some(Model {
+                gltf_file: Cow::Borrowed(&[]),
+                bin_file: Cow::Borrowed(&[]),
+                texture_file: match self.texture_path {
+                    Some(_) => None,
+                    None => self.texture_file.clone(),
+                },
+                resolver: self.resolver.clone(),
+                path: self.path.clone(),
+                texture_path: self.texture_path.clone(),
+                material: self.material.clone(),
+                ..*self
+            });
+        }
+
+        Ok(data)
+    }
+
+    /// Reads the files of a model made with [`Model::from_path`] again, keeping
+    /// every other setting.
+    pub fn reload(&self) -> Result<Model, ModelError> {
+        let Some(path) = &self.path else {
+            return Err(ModelError::Io {
+                path: String::new(),
+                reason: "the model wasn't read from a file".to_string(),
+            });
+        };
+
+        let mut model = Model::from_path(path)?;
+        model.texture_file = self.texture_file.clone();
+        if let Some(texture_path) = &self.texture_path {
+            model = model.set_texture_path(texture_path)?;
+        }
+        model.resolver = self.resolver.clone();
+        model.normal_mode = self.normal_mode;
+        model.position = self.position;
+        model.scale = self.scale;
+        model.rotation = self.rotation;
+        model.material = self.material.clone();
+
+        Ok(model)
+    }
+
+    /// The model file, its texture file and the external files its buffers and images
+    /// point to.
+    fn watched_files(&self, path: &Path, json: &JsonValue) -> Vec<PathBuf> {
+        let directory = path.parent().unwrap_or(Path::new(""));
+        let uris = json["buffers"]
+            .members()
+            .chain(json["images"].members())
+            .filter_map(|item| item["uri"].as_str())
+            .filter(|uri| !uri.starts_with("data:"));
+
+        let mut files = vec![path.to_path_buf()];
+        files.extend(self.texture_path.clone());
+        files.extend(uris.filter_map(|uri| resolver::uri_to_path(directory, uri).ok()));
+
+        files
+    }
+}
+
+/// A primitive with its vertices assembled, waiting to be uploaded.
+struct PrimitiveData {
+    node: usize,
+    key: assets::MeshKey,
+    vertices: Vec<f32>,
+    indices: Vec<u32>,
+    mode: gl::types::GLenum,
+    material: Option<usize>,
+    skinned: bool,
+    targets: Vec<mesh::MorphTarget>,
+}
+
+/// A model that was parsed and had its images decoded, but isn't on the GPU yet.
+/// Made by [`Model::load`] without a GL context, turned into a [`ReadyModel`] by
+/// [`ReadyModel::upload`].
+pub struct ModelData {
+    json: JsonValue,
+    buffers: Vec<Vec<u8>>,
+    resolver: Option<Arc<dyn resolver::Resolver>>,
+    normal_mode: types::NormalMode,
+    /// [`assets::content_key`] of the glTF JSON and buffers, for sharing mesh buffers.
+    model_key: u64,
+    nodes: Vec<Node>,
+    /// Nodes reachable from the scene, parents always ahead of their children.
+    node_order: Vec<(usize, Option<usize>)>,
+    skins: Vec<Skin>,
+    clips: Vec<animation::AnimationClip>,
+    override_image: Option<texture::Image>,
+    /// Images used by a material, `None` for external images without a resolver.
+    images: HashMap<usize, Option<texture::Image>>,
+    primitives: Vec<PrimitiveData>,
+    position: nalgebra_glm::Vec3,
+    scale: nalgebra_glm::Vec3,
+    rotation: nalgebra_glm::Quat,
+    material: Option<material::Material>,
+    /// Images of the material's texture uniforms, by uniform name.
+    material_images: Vec<(String, texture::Image)>,
+    /// Settings of the model for reading it again, without its file contents.
+    source: Option<Model>,
+    watched_files: Vec<PathBuf>,
+}
+
+impl ModelData {
+    pub fn load(
+        gltf_file: &[u8],
+        bin_file: &[u8],
+        texture_file: Option<&[u8]>,
+        normal_mode: types::NormalMode,
+        resolver: Option<Arc<dyn resolver::Resolver>>,
+    ) -> Result<ModelData, ModelError> {
+        let json_str = std::str::from_utf8(gltf_file)
+            .map_err(|err| ModelError::InvalidJson(err.to_string()))?;
+        let json = json::parse(json_str).map_err(|err| ModelError::InvalidJson(err.to_string()))?;
+
+        let buffers = load_buffers(&json, bin_file, resolver.as_deref())?;
+        let mut content: Vec<&[u8]> = vec![gltf_file];
+        content.extend(buffers.iter().map(|buffer| buffer.as_slice()));
+        let model_key = assets::content_key(&content);
+
+        let nodes = (0..json["nodes"].len())
+            .map(|ind_node| parse_node(&json, ind_node))
+            .collect::<Result<Vec<Node>, ModelError>>()?;
+
+        let mut node_order = Vec::new();
+        for root_node in root_nodes(&json) {
+            traverse_node(&mut node_order, &nodes, root_node, None, 0)?;
+        }
+
+        let skins = (0..json["skins"].len())
+            .map(|ind_skin| load_skin(&json, &buffers, nodes.len(), ind_skin))
+            .collect::<Result<Vec<Skin>, ModelError>>()?;
+
+        let clips = (0..json["animations"].len())
+            .map(|ind_animation| load_animation(&json, &buffers, nodes.len(), ind_animation))
+            .collect::<Result<Vec<animation::AnimationClip>, ModelError>>()?;
+
+        let override_image = texture_file
+            .map(texture::Image::decode)
+            .transpose()
+            .map_err(|reason| ModelError::BadImage {
+                image: None,
+                reason,
+            })?;
+
+        let mut data = ModelData {
+            json,
+            buffers,
+            resolver,
+            normal_mode,
+            model_key,
+            nodes,
+            node_order,
+            skins,
+            clips,
+            override_image,
+            images: HashMap::new(),
+            primitives: Vec::new(),
+            position: nalgebra_glm::vec3(0.0, 0.0, 0.0),
+            scale: nalgebra_glm::vec3(0.0, 0.0, 0.0),
+            rotation: nalgebra_glm::quat_identity(),
+            material: None,
+            material_images: Vec::new(),
+            source: None,
+            watched_files: Vec::new(),
+        };
+
+        for ind_order in 0..data.node_order.len() {
+            let ind_node = data.node_order[ind_order].0;
+            let node = &data.nodes[ind_node];
+            if let Some(ind_mesh) = node.mesh {
+                let skin = node.skin;
+                data.load_mesh(ind_mesh, ind_node, skin)?;
+            }
+        }
+
+        // Only base color images end up on the GPU, and none when the texture is overridden
+        if data.override_image.is_none() {
+            for ind_primitive in 0..data.primitives.len() {
+                if let Some(ind_image) =
+                    data.base_color_image(data.primitives[ind_primitive].material)
+                {
+                    data.load_image(ind_image)?;
+                }
+            }
+        }
+
+        Ok(data)
+    }
+
+    /// The image behind the base color texture of a material, if it has one.
+    fn base_color_image(&self, ind_material: Option<usize>) -> Option<usize> {
+        let ind_texture = self.json["materials"][ind_material?]["pbrMetallicRoughness"]
+            ["baseColorTexture"]["index"]
+            .as_usize()?;
+
+        self.json["textures"][ind_texture]["source"].as_usize()
+    }
+
+    /// Decodes an image once. Images that aren't available (external files without a
+    /// resolver) are kept as `None`, images that are present but can't be decoded are an error.
+    fn load_image(&mut self, ind_image: usize) -> Result<(), ModelError> {
+        if self.images.contains_key(&ind_image) {
+            return Ok(());
+        }
+
+        let image = match self.image_bytes(ind_image)? {
+            Some(bytes) => {
+                Some(
+                    texture::Image::decode(&bytes).map_err(|reason| ModelError::BadImage {
+                        image: Some(ind_image),
+                        reason,
+                    })?,
+                )
+            }
+            None => None,
+        };
+        self.images.insert(ind_image, image);
+
+        Ok(())
+    }
+
+    fn upload_material(
+        &self,
+        assets: &mut assets::AssetServer,
+        textures: &mut HashMap<usize, Rc<texture::Texture>>,
+        override_texture: Option<&Rc<texture::Texture>>,
+        ind_material: Option<usize>,
+    ) -> mesh::PbrMaterial {
+        let material = match ind_material {
+            Some(ind_material) => &self.json["materials"][ind_material],
+            None => &JsonValue::Null,
+        };
+        let pbr = &material["pbrMetallicRoughness"];
+
+        let mut base_color: [f32; 4] = [1.0; 4];
+        for i in 0..pbr["baseColorFactor"].len().min(4) {
+            base_color[i] = pbr["baseColorFactor"][i].as_f32().unwrap_or(1.0);
+        }
+
+        let texture = match (
+            override_texture,
+            pbr["baseColorTexture"]["index"].as_usize(),
+        ) {
+            (Some(override_texture), _) => override_texture.clone(),
+            (None, Some(ind_texture)) => self.upload_texture(assets, textures, ind_texture),
+            (None, None) => assets.white_texture(),
+        };
+
+        let alpha_mode = match material["alphaMode"].as_str() {
+            Some("MASK") => mesh::AlphaMode::Mask,
+            Some("BLEND") => mesh::AlphaMode::Blend,
+            _ => mesh::AlphaMode::Opaque,
+        };
+
+        mesh::PbrMaterial {
+            base_color: nalgebra_glm::make_vec4(&base_color),
+            texture,
+            alpha_mode,
+            alpha_cutoff: material["alphaCutoff"].as_f32().unwrap_or(0.5),
+            double_sided: material["doubleSided"].as_bool().unwrap_or(false),
+        }
+    }
+
+    fn upload_texture(
+        &self,
+        assets: &mut assets::AssetServer,
+        textures: &mut HashMap<usize, Rc<texture::Texture>>,
+        ind_texture: usize,
+    ) -> Rc<texture::Texture> {
+        if let Some(texture) = textures.get(&ind_texture) {
+            return texture.clone();
+        }
+
+        let gltf_texture = &self.json["textures"][ind_texture];
+        let sampler = interpret_sampler(
+            &self.json["samplers"][gltf_texture["sampler"].as_usize().unwrap_or(usize::MAX)],
+        );
+
+        let image = gltf_texture["source"]
+            .as_usize()
+            .and_then(|ind_image| self.images.get(&ind_image)?.as_ref());
+        let texture = match image {
+            Some(image) => assets.texture(image, sampler),
+            None => assets.white_texture(),
+        };
+        textures.insert(ind_texture, texture.clone());
+
+        texture
+    }
+
+    /// Reads a float accessor that has to hold `components` values per element.
+    fn get_floats(&self, ind_accessor: usize, components: usize) -> Result<Vec<f32>, ModelError> {
+        let (values, accessor_components) =
+            read_accessor_f32(&self.json, &self.buffers, ind_accessor)?;
+        if accessor_components != components {
+            return Err(ModelError::InvalidJson(format!(
+                "accessors[{}] has {} components per element, expected {}",
+                ind_accessor, accessor_components, components
+            )));
+        }
+
+        Ok(values)
+    }
+
+    fn get_indices(&self, ind_accessor: usize) -> Result<Vec<u32>, ModelError> {
+        let (indices, components) = read_accessor_u32(&self.json, &self.buffers, ind_accessor)?;
+        if components != 1 {
+            return Err(ModelError::InvalidJson(format!(
+                "accessors[{}] used as indices must be SCALAR",
+                ind_accessor
+            )));
+        }
+
+        Ok(indices)
+    }
+
+    /// Reads JOINTS_0, checking every index against the joints of the skin.
+    fn get_joints(
+        &self,
+        ind_accessor: usize,
+        joint_count: usize,
+    ) -> Result<Vec<nalgebra_glm::Vec4>, ModelError> {
+        let (joints, components) = read_accessor_u32(&self.json, &self.buffers, ind_accessor)?;
+        if components != 4 {
+            return Err(ModelError::InvalidJson(format!(
+                "accessors[{}] used as JOINTS_0 must be VEC4",
+                ind_accessor
+            )));
+        }
+        if joints.iter().any(|joint| *joint as usize >= joint_count) {
+            return Err(ModelError::AccessorOutOfBounds {
+                accessor: ind_accessor,
+            });
+        }
+
+        Ok(group_floats_vec4(
+            joints.into_iter().map(|joint| joint as f32).collect(),
+        ))
+    }
+
+    fn load_mesh(
+        &mut self,
+        ind_mesh: usize,
+        ind_node: usize,
+        skin: Option<usize>,
+    ) -> Result<(), ModelError> {
+        if !self.json["meshes"][ind_mesh].is_object() {
+            return Err(ModelError::MissingField(format!("meshes[{}]", ind_mesh)));
+        }
+
+        let primitive_count = self.json["meshes"][ind_mesh]["primitives"].len();
+
+        for ind_primitive in 0..primitive_count {
+            self.load_primitive(ind_mesh, ind_primitive, ind_node, skin)?;
+        }
+
+        Ok(())
+    }
+
+    fn load_primitive(
+        &mut self,
+        ind_mesh: usize,
+        ind_primitive: usize,
+        ind_node: usize,
+        skin: Option<usize>,
+    ) -> Result<(), ModelError> {
+        let primitive = self.json["meshes"][ind_mesh]["primitives"][ind_primitive].clone();
+
+        let attributes = &primitive["attributes"];
+        let pos_acc_ind =
+            attributes["POSITION"]
+                .as_usize()
+                .ok_or_else(|| ModelError::MissingAttribute {
+                    mesh: ind_mesh,
+                    primitive: ind_primitive,
+                    attribute: "POSITION".to_string(),
+                })?;
+
+        let mode_value = primitive["mode"].as_u32().unwrap_or(4);
+        let mut mode = interpret_mode(mode_value).map_err(|_| {
+            ModelError::InvalidJson(format!(
+                "meshes[{}].primitives[{}].mode {} is not between 0 and 6",
+                ind_mesh, ind_primitive, mode_value
+            ))
+        })?;
+        let material = primitive["material"].as_usize();
+        let skinned = skin.is_some() && !attributes["JOINTS_0"].is_null();
+
+        let key = assets::MeshKey {
+            model: self.model_key,
+            mesh: ind_mesh,
+            primitive: ind_primitive,
+            flat_normals: self.normal_mode == types::NormalMode::Flat,
+            skin,
+        };
+
+        let mut positions = group_floats_vec3(self.get_floats(pos_acc_ind, 3)?);
+        let vertex_count = positions.len();
+
+        // Every other attribute has to provide a value for each vertex
+        let per_vertex = |ind_accessor: usize, len: usize| {
+            if len == vertex_count {
+                Ok(())
+            } else {
+                Err(ModelError::AccessorOutOfBounds {
+                    accessor: ind_accessor,
+                })
+            }
+        };
+
+        let mut tex_uvs = match attributes["TEXCOORD_0"].as_usize() {
+            Some(tex_acc_ind) => {
+                let tex_uvs = group_floats_vec2(self.get_floats(tex_acc_ind, 2)?);
+                per_vertex(tex_acc_ind, tex_uvs.len())?;
+                tex_uvs
+            }
+            None => vec![nalgebra_glm::vec2(0.0, 0.0); vertex_count],
+        };
+
+        // COLOR_0 may be RGB or RGBA, as floats or normalized integers
+        let mut colors = match attributes["COLOR_0"].as_usize() {
+            Some(color_acc_ind) => {
+                let (values, components) =
+                    read_accessor_f32(&self.json, &self.buffers, color_acc_ind)?;
+                let colors = match components {
+                    3 => group_floats_vec3(values)
+                        .iter()
+                        .map(|rgb| nalgebra_glm::vec4(rgb.x, rgb.y, rgb.z, 1.0))
+                        .collect(),
+                    4 => group_floats_vec4(values),
+                    _ => {
+                        return Err(ModelError::InvalidJson(format!(
+                            "accessors[{}] used as COLOR_0 must be VEC3 or VEC4",
+                            color_acc_ind
+                        )))
+                    }
+                };
+                per_vertex(color_acc_ind, colors.len())?;
+                colors
+            }
+            None => vec![nalgebra_glm::vec4(1.0, 1.0, 1.0, 1.0); vertex_count],
+        };
+
+        // Joints and weights only matter when the node using this mesh has a skin
+        let (mut joints, mut weights) = match (
+            skin,
+            attributes["JOINTS_0"].as_usize(),
+            attributes["WEIGHTS_0"].as_usize(),
+        ) {
+            (Some(ind_skin), Some(joints_acc_ind), Some(weights_acc_ind)) => {
+                let joints = self.get_joints(joints_acc_ind, self.skins[ind_skin].joints.len())?;
+                per_vertex(joints_acc_ind, joints.len())?;
+                let weights = group_floats_vec4(self.get_floats(weights_acc_ind, 4)?)
+                    .iter()
+                    .map(normalize_weights)
+                    .collect::<Vec<nalgebra_glm::Vec4>>();
+                per_vertex(weights_acc_ind, weights.len())?;
+                (joints, weights)
+            }
+            _ => (
+                vec![nalgebra_glm::vec4(0.0, 0.0, 0.0, 0.0); vertex_count],
+                vec![nalgebra_glm::vec4(0.0, 0.0, 0.0, 0.0); vertex_count],
+            ),
+        };
+        // Morph targets hold offsets, attributes a target leaves out don't move
+        let read_deltas = |delta: &JsonValue| match delta.as_usize() {
+            Some(delta_acc_ind) => {
+                let deltas = group_floats_vec3(self.get_floats(delta_acc_ind, 3)?);
+                per_vertex(delta_acc_ind, deltas.len())?;
+                Ok(deltas)
+            }
+            None => Ok(vec![nalgebra_glm::vec3(0.0, 0.0, 0.0); vertex_count]),
+        };
+        let mut targets = Vec::with_capacity(primitive["targets"].len());
+        for target in primitive["targets"].members() {
+            targets.push(mesh::MorphTarget {
+                positions: read_deltas(&target["POSITION"])?,
+                normals: read_deltas(&target["NORMAL"])?,
+            });
+        }
+
+        // Non-indexed primitives draw their vertices in order
+        let mut indices = match primitive["indices"].as_usize() {
+            Some(ind_acc_ind) => {
+                let indices = self.get_indices(ind_acc_ind)?;
+                if indices.iter().any(|index| *index as usize >= vertex_count) {
+                    return Err(ModelError::AccessorOutOfBounds {
+                        accessor: ind_acc_ind,
+                    });
+                }
+                indices
+            }
+            None => (0..vertex_count as u32).collect(),
+        };
+
+        let normals = match attributes["NORMAL"].as_usize() {
+            Some(normal_acc_ind) => {
+                let normals = group_floats_vec3(self.get_floats(normal_acc_ind, 3)?);
+                per_vertex(normal_acc_ind, normals.len())?;
+                normals
+            }
+            None => {
+                let triangles = triangles(mode, &indices);
+
+                if self.normal_mode == types::NormalMode::Flat && !triangles.is_empty() {
+                    // Flat shading needs its own vertex for every triangle corner
+                    let corners: Vec<u32> = triangles.concat();
+                    positions = unweld(&positions, &corners);
+                    tex_uvs = unweld(&tex_uvs, &corners);
+                    colors = unweld(&colors, &corners);
+                    joints = unweld(&joints, &corners);
+                    weights = unweld(&weights, &corners);
+                    for target in targets.iter_mut() {
+                        target.positions = unweld(&target.positions, &corners);
+                        target.normals = unweld(&target.normals, &corners);
+                    }
+                    indices = (0..corners.len() as u32).collect();
+                    mode = gl::TRIANGLES;
+
+                    flat_normals(&positions)
+                } else {
+                    smooth_normals(&positions, &triangles)
+                }
+            }
+        };
+
+        let vertices = assemble_vertices(positions, normals, colors, tex_uvs, joints, weights);
+        self.primitives.push(PrimitiveData {
+            node: ind_node,
+            key,
+            vertices,
+            indices,
+            mode,
+            material,
+            skinned,
+            targets,
+        });
+
+        Ok(())
+    }
+
+    /// Fetches the encoded bytes of an image from a bufferView or its URI. External
+    /// images are `None` when the model has no resolver.
+    fn image_bytes(&self, ind_image: usize) -> Result<Option<Vec<u8>>, ModelError> {
+        let image = &self.json["images"][ind_image];
+
+        if let Some(ind_buffer_view) = image["bufferView"].as_usize() {
+            let buffer_view = &self.json["bufferViews"][ind_buffer_view];
+            let buffer = buffer_view["buffer"].as_usize().unwrap_or(0);
+            let byte_offset = buffer_view["byteOffset"].as_usize().unwrap_or(0);
+            let byte_length = require(buffer_view["byteLength"].as_usize(), || {
+                format!("bufferViews[{}].byteLength", ind_buffer_view)
+            })?;
+
+            let bytes = byte_offset
+                .checked_add(byte_length)
+                .and_then(|end| self.buffers.get(buffer)?.get(byte_offset..end))
+                .ok_or_else(|| {
+                    ModelError::InvalidJson(format!(
+                        "bufferViews[{}] is outside buffers[{}]",
+                        ind_buffer_view, buffer
+                    ))
+                })?;
+            return Ok(Some(bytes.to_vec()));
+        }
+
+        let Some(uri) = image["uri"].as_str() else {
+            return Ok(None);
+        };
+        if self.resolver.is_none() && !uri.starts_with("data:") {
+            return Ok(None);
+        }
+
+        resolver::resolve_uri(uri, self.resolver.as_deref())
+            .map(Some)
+            .map_err(|reason| ModelError::UnresolvedUri {
+                uri: uri.to_string(),
+                reason,
+            })
+    }
+}
+
+/// Hands out the ids that tell models apart, see [`ReadyModel::id`].
+static NEXT_MODEL_ID: AtomicU64 = AtomicU64::new(0);
+
+pub struct ReadyModel {
+    gl: gl::Gl,
+    id: u64,
+    nodes: Vec<Node>,
+    /// Nodes reachable from the scene, parents always ahead of their children.
+    node_order: Vec<(usize, Option<usize>)>,
+    world_matrices: Vec<nalgebra_glm::Mat4>,
+    skins: Vec<Skin>,
+    animation_player: animation::AnimationPlayer,
+    /// Nodes the animation player posed on the last update.
+    animated_nodes: Vec<usize>,
+    /// Every mesh together with the node it's attached to.
+    meshes: Vec<(usize, mesh::Mesh)>,
+    material: Option<material::ReadyMaterial>,
+    position: nalgebra_glm::Vec3,
+    scale: nalgebra_glm::Vec3,
+    rotation: nalgebra_glm::Quat,
+    source: Option<Model>,
+    watched_files: Vec<PathBuf>,
+}
+
+impl ReadyModel {
+    pub unsafe fn new(
+        gl: gl::Gl,
+        gltf_file: &[u8],
+        bin_file: &[u8],
+        texture_file: Option<&[u8]>,
+        normal_mode: types::NormalMode,
+        resolver: Option<Arc<dyn resolver::Resolver>>,
+        assets: &mut assets::AssetServer,
+    ) -> Result<Self, ModelError> {
+        let data = ModelData::load(gltf_file, bin_file, texture_file, normal_mode, resolver)?;
+
+        ReadyModel::upload(gl, data, assets)
+    }
+
+    /// Uploads the textures and meshes of a loaded model, sharing what the asset
+    /// server already has. The context has to be current.
+    pub unsafe fn upload(
+        gl: gl::Gl,
+        mut data: ModelData,
+        assets: &mut assets::AssetServer,
+    ) -> Result<Self, ModelError> {
+        let material = match &data.material {
+            Some(material) => Some(
+                material::ReadyMaterial::new(gl.clone(), material, &data.material_images, assets)
+                    .map_err(ModelError::Shader)?,
+            ),
+            None => None,
+        };
+        let override_texture = data
+            .override_image
+            .as_ref()
+            .map(|image| assets.texture(image, texture::Sampler::default()));
+        let mut textures = HashMap::new();
+
+        let primitives = std::mem::take(&mut data.primitives);
+        let mut meshes = Vec::with_capacity(primitives.len());
+        for primitive in primitives {
+            let material = data.upload_material(
+                assets,
+                &mut textures,
+                override_texture.as_ref(),
+                primitive.material,
+            );
+
+            // Buffers of morphed meshes change per instance, all others can be shared
+            let shareable = primitive.targets.is_empty();
+            let mesh = match assets.mesh(&primitive.key).filter(|_| shareable) {
+                Some(buffers) => {
+                    mesh::Mesh::shared(gl.clone(), buffers, material, primitive.skinned)
+                }
+                None => {
+                    let mut mesh = mesh::Mesh::new(
+                        gl.clone(),
+                        primitive.vertices,
+                        primitive.indices,
+                        primitive.mode,
+                        material,
+                        primitive.skinned,
+                        primitive.targets,
+                    );
+                    mesh.set_morph_weights(&data.nodes[primitive.node].weights);
+                    if shareable {
+                        assets.insert_mesh(primitive.key, mesh.buffers());
+                    }
+                    mesh
+                }
+            };
+            meshes.push((primitive.node, mesh));
+        }
+
+        let mut instance = Self {
+            gl,
+            id: NEXT_MODEL_ID.fetch_add(1, Ordering::Relaxed),
+            world_matrices: vec![nalgebra_glm::Mat4::identity(); data.nodes.len()],
+            nodes: data.nodes,
+            node_order: data.node_order,
+            skins: data.skins,
+            animation_player: animation::AnimationPlayer::new(data.clips),
+            animated_nodes: Vec::new(),
+            meshes,
+            material,
+            position: data.position,
+            scale: data.scale,
+            rotation: data.rotation,
+            source: data.source,
+            watched_files: data.watched_files,
+        };
+        instance.update_world_matrices();
+
+        Ok(instance)
+    }
+
+    /// Swaps in a new version of the model, keeping its id and transform. The model
+    /// stays as it is if the new version can't be uploaded.
+    pub unsafe fn replace(
+        &mut self,
+        data: ModelData,
+        assets: &mut assets::AssetServer,
+    ) -> Result<(), ModelError> {
+        let mut model = ReadyModel::upload(self.gl.clone(), data, assets)?;
+        model.id = self.id;
+        model.position = self.position;
+        model.scale = self.scale;
+        model.rotation = self.rotation;
+
+        *self = model;
+
+        Ok(())
+    }
+
+    /// Stays the same for as long as the model lives, even across reloads.
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// The model as it was configured, if it was read from a file and can be reloaded.
+    pub fn source(&self) -> Option<&Model> {
+        self.source.as_ref()
+    }
+
+    /// The material the model is drawn with, if it was given one with [`Model::set_material`].
+    pub fn material(&self) -> Option<&material::ReadyMaterial> {
+        self.material.as_ref()
+    }
+
+    /// Lets the material's uniforms change while the model is shown.
+    pub fn material_mut(&mut self) -> Option<&mut material::ReadyMaterial> {
+        self.material.as_mut()
+    }
+
+    /// Draws every mesh with `texture` instead of its own base color texture, e.g.
+    /// the one of a [`crate::renderer::RenderTarget`] for a screen showing another view.
+    /// A reload brings back the textures of the file.
+    pub fn set_texture(&mut self, texture: Rc<texture::Texture>) {
+        for (_, mesh) in self.meshes.iter_mut() {
+            mesh.set_texture(texture.clone());
+        }
+    }
+
+    /// Files the model was loaded from, empty unless it came from [`Model::from_path`].
+    pub fn watched_files(&self) -> &[PathBuf] {
+        &self.watched_files
+    }
+
+    pub fn set_position(&mut self, position: nalgebra_glm::Vec3) {
+        self.position = position;
+    }
+
+    pub fn set_scale(&mut self, scale: nalgebra_glm::Vec3) {
+        self.scale = scale;
+    }
+
+    pub fn set_rotation(&mut self, rotation: nalgebra_glm::Quat) {
+        self.rotation = rotation;
+    }
+
+    pub fn skin_count(&self) -> usize {
+        self.skins.len()
+    }
+
+    pub fn joint_count(&self, skin: usize) -> usize {
+        self.skins.get(skin).map_or(0, |skin| skin.joints.len())
+    }
+
+    /// Finds a joint of `skin` by the name of its node.
+    pub fn joint_index(&self, skin: usize, name: &str) -> Option<usize> {
+        self.skins
+            .get(skin)?
+            .joints
+            .iter()
+            .position(|ind_node| self.nodes[*ind_node].name.as_deref() == Some(name))
+    }
+
+    /// Replaces the local transform of a joint, relative to its parent node.
+    /// Out of range skins or joints are ignored.
+    pub fn set_joint_pose(
+        &mut self,
+        skin: usize,
+        joint: usize,
+        translation: nalgebra_glm::Vec3,
+        rotation: nalgebra_glm::Quat,
+        scale: nalgebra_glm::Vec3,
+    ) {
+        let Some(ind_node) = self
+            .skins
+            .get(skin)
+            .and_then(|skin| skin.joints.get(joint).copied())
+        else {
+            return;
+        };
+
+        let node = &mut self.nodes[ind_node];
+        node.matrix = None;
+        node.translation = translation;
+        node.rotation = rotation;
+        node.scale = scale;
+
+        self.update_world_matrices();
+    }
+
+    /// Puts every node back into the transform it was loaded with.
+    pub fn reset_pose(&mut self) {
+        for node in self.nodes.iter_mut() {
+            node.matrix = node.rest_matrix;
+            (node.translation, node.rotation, node.scale) = node.rest_trs;
+            node.weights.clone_from(&node.rest_weights);
+        }
+
+        self.update_world_matrices();
+        for ind_node in 0..self.nodes.len() {
+            self.apply_morph_weights(ind_node);
+        }
+    }
+
+    /// Finds a node by name, for use with [`ReadyModel::set_morph_weights`].
+    pub fn node_index(&self, name: &str) -> Option<usize> {
+        self.nodes
+            .iter()
+            .position(|node| node.name.as_deref() == Some(name))
+    }
+
+    /// Number of morph targets of the mesh attached to `node`.
+    pub fn morph_target_count(&self, node: usize) -> usize {
+        self.meshes
+            .iter()
+            .filter(|(ind_node, _)| *ind_node == node)
+            .map(|(_, mesh)| mesh.morph_target_count())
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Sets the morph target weights of the mesh attached to `node`.
+    /// Missing weights count as zero, out of range nodes are ignored.
+    pub fn set_morph_weights(&mut self, node: usize, weights: &[f32]) {
+        let Some(gltf_node) = self.nodes.get_mut(node) else {
+            return;
+        };
+        gltf_node.weights = weights.to_vec();
+
+        self.apply_morph_weights(node);
+    }
+
+    fn apply_morph_weights(&mut self, ind_node: usize) {
+        let weights = &self.nodes[ind_node].weights;
+        for (_, mesh) in self
+            .meshes
+            .iter_mut()
+            .filter(|(mesh_node, _)| *mesh_node == ind_node)
+        {
+            mesh.set_morph_weights(weights);
+        }
+    }
+
+    pub fn animation_player(&mut self) -> &mut animation::AnimationPlayer {
+        &mut self.animation_player
+    }
+
+    /// Advances the animation player by `dt` seconds and poses the animated nodes.
+    /// Nodes that stop being animated go back to their loaded transform.
+    pub fn update(&mut self, dt: f32) {
+        if self.animated_nodes.is_empty() && self.animation_player.current_clip().is_none() {
+            return;
+        }
+
+        self.animation_player.advance(dt);
+
+        let nodes = &self.nodes;
+        let pose = self.animation_player.pose(|ind_node, path| {
+            let (translation, rotation, scale) = nodes[ind_node].rest_trs;
+            match path {
+                animation::ChannelPath::Translation => translation.as_slice().to_vec(),
+                animation::ChannelPath::Rotation => rotation.coords.as_slice().to_vec(),
+                animation::ChannelPath::Scale => scale.as_slice().to_vec(),
+                animation::ChannelPath::Weights => nodes[ind_node].rest_weights.clone(),
+            }
+        });
+
+        let mut morphed_nodes = Vec::new();
+        for ind_node in self.animated_nodes.drain(..) {
+            let node = &mut self.nodes[ind_node];
+            node.matrix = node.rest_matrix;
+            (node.translation, node.rotation, node.scale) = node.rest_trs;
+            if node.weights != node.rest_weights {
+                node.weights.clone_from(&node.rest_weights);
+                morphed_nodes.push(ind_node);
+            }
+        }
+
+        for (ind_node, path, value) in pose {
+            let node = &mut self.nodes[ind_node];
+            match path {
+                animation::ChannelPath::Translation => {
+                    node.matrix = None;
+                    node.translation = nalgebra_glm::make_vec3(&value);
+                }
+                animation::ChannelPath::Rotation => {
+                    node.matrix = None;
+                    node.rotation = nalgebra_glm::make_quat(&value);
+                }
+                animation::ChannelPath::Scale => {
+                    node.matrix = None;
+                    node.scale = nalgebra_glm::make_vec3(&value);
+                }
+                animation::ChannelPath::Weights => {
+                    node.weights = value;
+                    morphed_nodes.push(ind_node);
+                }
+            }
+            self.animated_nodes.push(ind_node);
+        }
+
+        self.update_world_matrices();
+
+        morphed_nodes.sort_unstable();
+        morphed_nodes.dedup();
+        for ind_node in morphed_nodes {
+            self.apply_morph_weights(ind_node);
+        }
+    }
+
+    fn update_world_matrices(&mut self) {
+        for (ind_node, parent) in self.node_order.iter() {
+            let parent_matrix = match parent {
+                Some(parent) => self.world_matrices[*parent],
+                None => nalgebra_glm::Mat4::identity(),
+            };
+            self.world_matrices[*ind_node] = parent_matrix * self.nodes[*ind_node].local_matrix();
+        }
+    }
+
+    pub fn draw(&self, program: &shader::ShaderProgram) {
+        let translation_matrix =
+            nalgebra_glm::translate(&nalgebra_glm::Mat4::identity(), &self.position);
+        let rotation_matrix = nalgebra_glm::quat_to_mat4(&self.rotation);
+        let scale_matrix = nalgebra_glm::scale(&nalgebra_glm::Mat4::identity(), &self.scale);
+        let model_matrix = translation_matrix * rotation_matrix * scale_matrix;
+
+        for (ind_node, mesh) in self.meshes.iter() {
+            match self.nodes[*ind_node].skin {
+                // Skinned meshes are placed by their joints alone, the node they're attached to doesn't move them
+                Some(ind_skin) => {
+                    let skin = &self.skins[ind_skin];
+                    let joint_matrices: Vec<nalgebra_glm::Mat4> = skin
+                        .joints
+                        .iter()
+                        .zip(skin.inverse_bind_matrices.iter())
+                        .map(|(ind_joint, inverse_bind)| {
+                            self.world_matrices[*ind_joint] * inverse_bind
+                        })
+                        .collect();
+                    mesh.draw(program, &model_matrix, &joint_matrices);
+                }
+                None => mesh.draw(
+                    program,
+                    &(model_matrix * self.world_matrices[*ind_node]),
+                    &[],
+                ),
+            }
+        }
+    }
+}
+
+/// Gathers every buffer of the glTF. The bin file passed to the model stands in for
+/// the first buffer, which is also where GLB files keep their binary chunk.
+fn load_buffers(
+    json: &JsonValue,
+    bin_file: &[u8],
+    resolver: Option<&dyn resolver::Resolver>,
+) -> Result<Vec<Vec<u8>>, ModelError> {
+    let mut buffers = Vec::with_capacity(json["buffers"].len());
+
+    for ind_buffer in 0..json["buffers"].len() {
+        let buffer = &json["buffers"][ind_buffer];
+        let bytes = match buffer["uri"].as_str() {
+            _ if ind_buffer == 0 && !bin_file.is_empty() => bin_file.to_vec(),
+            Some(uri) => resolver::resolve_uri(uri, resolver).map_err(|reason| {
+                ModelError::UnresolvedUri {
+                    uri: uri.to_string(),
+                    reason,
+                }
+            })?,
+            None => {
+                return Err(ModelError::MissingField(format!(
+                    "buffers[{}].uri",
+                    ind_buffer
+                )))
+            }
+        };
+
+        let byte_length = require(buffer["byteLength"].as_usize(), || {
+            format!("buffers[{}].byteLength", ind_buffer)
+        })?;
+        if bytes.len() < byte_length {
+            return Err(ModelError::InvalidJson(format!(
+                "buffers[{}] is {} bytes long, its byteLength says {}",
+                ind_buffer,
+                bytes.len(),
+                byte_length
+            )));
+        }
+
+        buffers.push(bytes);
+    }
+
+    Ok(buffers)
+}
+
+/// Reads a node's transform, mesh and skin. Hierarchy checks happen in [`traverse_node`].
+fn parse_node(json: &JsonValue, ind_node: usize) -> Result<Node, ModelError> {
+    let node = &json["nodes"][ind_node];
+    if !node.is_object() {
+        return Err(ModelError::MissingField(format!("nodes[{}]", ind_node)));
+    }
+
+    let mut translation: nalgebra_glm::Vec3 = nalgebra_glm::vec3(0.0, 0.0, 0.0);
+    if !node["translation"].is_null() {
+        let mut trans_values: [f32; 3] = [0.0; 3];
+        read_floats(&node["translation"], &mut trans_values, || {
+            format!("nodes[{}].translation", ind_node)
+        })?;
+        translation = nalgebra_glm::make_vec3(&trans_values);
+    }
+
+    // glTF stores rotations as [x, y, z, w], which is also the order make_quat expects
+    let mut rotation: nalgebra_glm::Quat = nalgebra_glm::quat_identity();
+    if !node["rotation"].is_null() {
+        let mut rotation_values: [f32; 4] = [0.0; 4];
+        read_floats(&node["rotation"], &mut rotation_values, || {
+            format!("nodes[{}].rotation", ind_node)
+        })?;
+        rotation = nalgebra_glm::make_quat(&rotation_values)
+    }
+
+    let mut scale: nalgebra_glm::Vec3 = nalgebra_glm::vec3(1.0, 1.0, 1.0);
+    if !node["scale"].is_null() {
+        let mut scale_values: [f32; 3] = [0.0; 3];
+        read_floats(&node["scale"], &mut scale_values, || {
+            format!("nodes[{}].scale", ind_node)
+        })?;
+        scale = nalgebra_glm::make_vec3(&scale_values);
+    }
+
+    let mut matrix = None;
+    if !node["matrix"].is_null() {
+        let mut mat_values: [f32; 16] = [0.0; 16];
+        read_floats(&node["matrix"], &mut mat_values, || {
+            format!("nodes[{}].matrix", ind_node)
+        })?;
+        matrix = Some(nalgebra_glm::make_mat4(&mat_values));
+    }
+
+    let mut children = Vec::with_capacity(node["children"].len());
+    for i in 0..node["children"].len() {
+        children.push(require(node["children"][i].as_usize(), || {
+            format!("nodes[{}].children[{}]", ind_node, i)
+        })?);
+    }
+
+    let mut mesh = None;
+    if !node["mesh"].is_null() {
+        mesh = Some(require(node["mesh"].as_usize(), || {
+            format!("nodes[{}].mesh", ind_node)
+        })?);
+    }
 
-impl Model {
-    pub fn new(
-        gltf_file: &'static [u8],
-        bin_file: &'static [u8],
-        texture_file: &'static [u8],
-    ) -> Model {
-        return Model {
-            gltf_file,
-            bin_file,
-            texture_file,
-            position: types::Vec3::new(0.0, 0.0, 0.0),
-            scale: types::Vec3::new(0.01, 0.01, 0.01),
-            rotation: (0.0, types::RotAxis::Pitch),
-        };
+    let mut skin = None;
+    if !node["skin"].is_null() {
+        let ind_skin = require(node["skin"].as_usize(), || {
+            format!("nodes[{}].skin", ind_node)
+        })?;
+        if ind_skin >= json["skins"].len() {
+            return Err(ModelError::MissingField(format!("skins[{}]", ind_skin)));
+        }
+        skin = Some(ind_skin);
     }
 
-    pub fn set_position(mut self, position: types::Vec3) -> Self {
-        self.position = position;
-        self
+    // Nodes can override the default weights of their mesh
+    let mut weights = Vec::new();
+    let weights_json = match (&node["weights"], mesh) {
+        (JsonValue::Array(_), _) => &node["weights"],
+        (_, Some(ind_mesh)) => &json["meshes"][ind_mesh]["weights"],
+        (_, None) => &JsonValue::Null,
+    };
+    for i in 0..weights_json.len() {
+        weights.push(require(weights_json[i].as_f32(), || {
+            format!("nodes[{}] weights[{}]", ind_node, i)
+        })?);
     }
 
-    pub fn set_scale(mut self, scale: types::Vec3) -> Self {
-        self.scale = scale;
-        self
+    Ok(Node {
+        name: node["name"].as_str().map(|name| name.to_string()),
+        children,
+        matrix,
+        translation,
+        rotation,
+        scale,
+        rest_matrix: matrix,
+        rest_trs: (translation, rotation, scale),
+        rest_weights: weights.clone(),
+        weights,
+        mesh,
+        skin,
+    })
+}
+
+/// Appends `ind_node` and its descendants to `order`, each paired with its parent.
+fn traverse_node(
+    order: &mut Vec<(usize, Option<usize>)>,
+    nodes: &[Node],
+    ind_node: usize,
+    parent: Option<usize>,
+    depth: usize,
+) -> Result<(), ModelError> {
+    let node = nodes
+        .get(ind_node)
+        .ok_or_else(|| ModelError::MissingField(format!("nodes[{}]", ind_node)))?;
+    // A valid hierarchy can't be deeper than the number of nodes
+    if depth > nodes.len() {
+        return Err(ModelError::InvalidJson(
+            "nodes contain a cycle in their children".to_string(),
+        ));
     }
 
-    pub fn set_rotation(mut self, rotation: f32, axis: types::RotAxis) -> Self {
-        self.rotation = (rotation, axis);
-        self
+    order.push((ind_node, parent));
+
+    for child in node.children.iter() {
+        traverse_node(order, nodes, *child, Some(ind_node), depth + 1)?;
     }
+
+    Ok(())
 }
 
-pub struct ReadyModel {
-    gl: gl::Gl,
-    json: JsonValue,
-    data: Vec<u8>,
-    texture_file: &'static [u8],
-    meshes: Vec<mesh::Mesh>,
-    position: nalgebra_glm::Vec3,
-    scale: nalgebra_glm::Vec3,
-    rotation: nalgebra_glm::Quat,
+fn load_skin(
+    json: &JsonValue,
+    buffers: &[Vec<u8>],
+    node_count: usize,
+    ind_skin: usize,
+) -> Result<Skin, ModelError> {
+    let skin = &json["skins"][ind_skin];
+
+    let mut joints = Vec::with_capacity(skin["joints"].len());
+    for i in 0..skin["joints"].len() {
+        let joint = require(skin["joints"][i].as_usize(), || {
+            format!("skins[{}].joints[{}]", ind_skin, i)
+        })?;
+        if joint >= node_count {
+            return Err(ModelError::MissingField(format!("nodes[{}]", joint)));
+        }
+        joints.push(joint);
+    }
+    if joints.len() > mesh::MAX_JOINTS {
+        return Err(ModelError::TooManyJoints {
+            skin: ind_skin,
+            joints: joints.len(),
+        });
+    }
+
+    // Without inverse bind matrices every joint is bound at the identity
+    let inverse_bind_matrices = match skin["inverseBindMatrices"].as_usize() {
+        Some(ind_accessor) => {
+            let (values, components) = read_accessor_f32(json, buffers, ind_accessor)?;
+            if components != 16 {
+                return Err(ModelError::InvalidJson(format!(
+                    "accessors[{}] used as inverseBindMatrices must be MAT4",
+                    ind_accessor
+                )));
+            }
+            if values.len() / 16 < joints.len() {
+                return Err(ModelError::AccessorOutOfBounds {
+                    accessor: ind_accessor,
+                });
+            }
+            values
+                .chunks_exact(16)
+                .map(nalgebra_glm::make_mat4)
+                .collect()
+        }
+        None => vec![nalgebra_glm::Mat4::identity(); joints.len()],
+    };
+
+    Ok(Skin {
+        joints,
+        inverse_bind_matrices,
+    })
 }
 
-impl ReadyModel {
-    pub unsafe fn new(
-        gl: gl::Gl,
-        gltf_file: &'static [u8],
-        bin_file: &[u8],
-        texture_file: &'static [u8],
-    ) -> Self {
-        let json = json::parse(std::str::from_utf8(&gltf_file).unwrap()).unwrap();
-
-        let mut state = TraverseState {
-            translations_meshes: Vec::new(),
-            rotations_meshes: Vec::new(),
-            scales_meshes: Vec::new(),
-            meshes_to_load: Vec::new(),
-        };
+fn load_animation(
+    json: &JsonValue,
+    buffers: &[Vec<u8>],
+    node_count: usize,
+    ind_animation: usize,
+) -> Result<animation::AnimationClip, ModelError> {
+    let gltf_animation = &json["animations"][ind_animation];
+    let gltf_channels = &gltf_animation["channels"];
 
-        ReadyModel::traverse_node(&mut state, json.clone(), 0, nalgebra_glm::Mat4::identity());
+    let mut channels = Vec::with_capacity(gltf_channels.len());
+    for ind_channel in 0..gltf_channels.len() {
+        let channel = &gltf_channels[ind_channel];
+        let path_str = || format!("animations[{}].channels[{}]", ind_animation, ind_channel);
 
-        let mut instance = Self {
-            gl,
-            json,
-            data: bin_file.to_vec(),
-            texture_file,
-            meshes: Vec::new(),
-            position: nalgebra_glm::vec3(0.0, 0.0, 0.0),
-            scale: nalgebra_glm::vec3(0.0, 0.0, 0.0),
-            rotation: nalgebra_glm::quat_identity(),
+        // Channels without a node are meant for extensions
+        let Some(node) = channel["target"]["node"].as_usize() else {
+            continue;
+        };
+        if node >= node_count {
+            return Err(ModelError::MissingField(format!("nodes[{}]", node)));
+        }
+        // Weights are stored as scalars, one per morph target of the node's mesh
+        let (path, element_components, components) = match channel["target"]["path"].as_str() {
+            Some("translation") => (animation::ChannelPath::Translation, 3, 3),
+            Some("rotation") => (animation::ChannelPath::Rotation, 4, 4),
+            Some("scale") => (animation::ChannelPath::Scale, 3, 3),
+            Some("weights") => {
+                let ind_mesh = json["nodes"][node]["mesh"].as_usize().unwrap_or(usize::MAX);
+                let targets = json["meshes"][ind_mesh]["primitives"][0]["targets"].len();
+                if targets == 0 {
+                    continue;
+                }
+                (animation::ChannelPath::Weights, 1, targets)
+            }
+            Some(_) => continue,
+            None => {
+                return Err(ModelError::MissingField(format!(
+                    "{}.target.path",
+                    path_str()
+                )))
+            }
+        };
+
+        let ind_sampler = require(channel["sampler"].as_usize(), || {
+            format!("{}.sampler", path_str())
+        })?;
+        let sampler = &gltf_animation["samplers"][ind_sampler];
+        if !sampler.is_object() {
+            return Err(ModelError::MissingField(format!(
+                "animations[{}].samplers[{}]",
+                ind_animation, ind_sampler
+            )));
+        }
+        let interpolation = match sampler["interpolation"].as_str() {
+            None | Some("LINEAR") => animation::Interpolation::Linear,
+            Some("STEP") => animation::Interpolation::Step,
+            Some("CUBICSPLINE") => animation::Interpolation::CubicSpline,
+            Some(other) => {
+                return Err(ModelError::InvalidJson(format!(
+                    "animations[{}].samplers[{}] has unknown interpolation {}",
+                    ind_animation, ind_sampler, other
+                )))
+            }
         };
 
-        for mesh_index in state.meshes_to_load {
-            ReadyModel::load_mesh(&mut instance, mesh_index);
+        let ind_input = require(sampler["input"].as_usize(), || {
+            format!(
+                "animations[{}].samplers[{}].input",
+                ind_animation, ind_sampler
+            )
+        })?;
+        let ind_output = require(sampler["output"].as_usize(), || {
+            format!(
+                "animations[{}].samplers[{}].output",
+                ind_animation, ind_sampler
+            )
+        })?;
+        let (input, input_components) = read_accessor_f32(json, buffers, ind_input)?;
+        let (output, output_components) = read_accessor_f32(json, buffers, ind_output)?;
+        if input_components != 1 || input.is_empty() {
+            return Err(ModelError::InvalidJson(format!(
+                "accessors[{}] used as animation input must be a non-empty SCALAR",
+                ind_input
+            )));
+        }
+        if output_components != element_components {
+            return Err(ModelError::InvalidJson(format!(
+                "accessors[{}] has {} components per element, expected {}",
+                ind_output, output_components, element_components
+            )));
+        }
+
+        // Cubic splines store an in-tangent, value and out-tangent for every keyframe
+        let elements_per_key = match interpolation {
+            animation::Interpolation::CubicSpline => 3,
+            animation::Interpolation::Linear | animation::Interpolation::Step => 1,
+        };
+        if output.len() < input.len() * elements_per_key * components {
+            return Err(ModelError::AccessorOutOfBounds {
+                accessor: ind_output,
+            });
         }
 
-        instance
+        channels.push(animation::AnimationChannel {
+            node,
+            path,
+            sampler: animation::AnimationSampler {
+                input,
+                output,
+                components,
+                interpolation,
+            },
+        });
     }
 
-    pub fn set_position(&mut self, position: nalgebra_glm::Vec3) {
-        self.position = position;
+    Ok(animation::AnimationClip::new(
+        gltf_animation["name"].as_str().map(|name| name.to_string()),
+        channels,
+    ))
+}
+
+/// Turns an absent or mistyped JSON property into a [`ModelError::MissingField`].
+fn require<T>(value: Option<T>, path: impl FnOnce() -> String) -> Result<T, ModelError> {
+    value.ok_or_else(|| ModelError::MissingField(path()))
+}
+
+/// Fills `values` from a JSON array of numbers, which has to be exactly as long.
+fn read_floats(
+    array: &JsonValue,
+    values: &mut [f32],
+    path: impl Fn() -> String,
+) -> Result<(), ModelError> {
+    if !array.is_array() || array.len() != values.len() {
+        return Err(ModelError::InvalidJson(format!(
+            "{} must be an array of {} numbers",
+            path(),
+            values.len()
+        )));
     }
 
-    pub fn set_scale(&mut self, scale: nalgebra_glm::Vec3) {
-        self.scale = scale;
+    for (i, value) in values.iter_mut().enumerate() {
+        *value = require(array[i].as_f32(), || format!("{}[{}]", path(), i))?;
     }
 
-    pub fn set_rotation(&mut self, rotation: nalgebra_glm::Quat) {
-        self.rotation = rotation;
+    Ok(())
+}
+
+/// Returns the nodes the default scene starts from. Files without scenes have
+/// every node that isn't referenced as a child treated as a root.
+fn root_nodes(json: &JsonValue) -> Vec<usize> {
+    let scene_index = json["scene"].as_usize().unwrap_or(0);
+    let scene = &json["scenes"][scene_index];
+
+    if !scene["nodes"].is_null() {
+        return scene["nodes"]
+            .members()
+            .filter_map(|node| node.as_usize())
+            .collect();
     }
 
-    pub fn draw(&self, program: gl::types::GLuint) {
-        for mesh in self.meshes.iter() {
-            mesh.draw(program, self.position, self.scale, self.rotation);
+    let mut is_child = vec![false; json["nodes"].len()];
+    for node in json["nodes"].members() {
+        for child in node["children"].members() {
+            if let Some(is_child) = child.as_usize().and_then(|c| is_child.get_mut(c)) {
+                *is_child = true;
+            }
         }
     }
 
-    fn get_floats(&mut self, accessor: JsonValue) -> Vec<f32> {
-        let buff_view_ind = if accessor["bufferView"].is_null() {
-            1
-        } else {
-            accessor["bufferView"].as_u64().unwrap() as usize
-        };
-        let count = accessor["count"].as_u64().unwrap() as usize;
-        let acc_byte_offset = if accessor["byteOffset"].is_null() {
-            0
-        } else {
-            accessor["byteOffset"].as_u64().unwrap() as usize
-        };
-        let type_str = accessor["type"].as_str().unwrap();
+    (0..is_child.len())
+        .filter(|node| !is_child[*node])
+        .collect()
+}
+
+const GLB_MAGIC: u32 = 0x4654_6C67;
+const GLB_CHUNK_JSON: u32 = 0x4E4F_534A;
+const GLB_CHUNK_BIN: u32 = 0x004E_4942;
+
+fn read_u32_le(bytes: &[u8], offset: usize) -> Option<u32> {
+    let slice = bytes.get(offset..offset.checked_add(4)?)?;
+    Some(u32::from_le_bytes([slice[0], slice[1], slice[2], slice[3]]))
+}
+
+/// Splits a binary glTF container into its JSON chunk and (possibly empty) BIN chunk.
+fn parse_glb(glb: &[u8]) -> Result<(&[u8], &[u8]), ModelError> {
+    let magic = read_u32_le(glb, 0).ok_or_else(|| glb_error("GLB header is truncated"))?;
+    if magic != GLB_MAGIC {
+        return Err(glb_error("GLB magic is invalid (expected \"glTF\")"));
+    }
 
-        let buffer_view = &self.json["bufferViews"][buff_view_ind];
-        let byte_offset = buffer_view["byteOffset"].as_u64().unwrap() as usize;
+    let version = read_u32_le(glb, 4).ok_or_else(|| glb_error("GLB header is truncated"))?;
+    if version != 2 {
+        return Err(ModelError::InvalidGlb(format!(
+            "GLB version {} is unsupported (expected 2)",
+            version
+        )));
+    }
+
+    let length = read_u32_le(glb, 8).ok_or_else(|| glb_error("GLB header is truncated"))? as usize;
+    if length != glb.len() {
+        return Err(ModelError::InvalidGlb(format!(
+            "GLB declares {} bytes but has {}",
+            length,
+            glb.len()
+        )));
+    }
 
-        let num_per_vert = interpret_type(type_str).expect("test");
+    let mut json_chunk: Option<&[u8]> = None;
+    let mut bin_chunk: &[u8] = &[];
+    let mut offset = 12;
 
-        let beginning_of_data = byte_offset + acc_byte_offset;
-        let length_of_data = count * 4 * num_per_vert;
-        let mut float_vec = Vec::with_capacity(count * num_per_vert);
+    while offset < glb.len() {
+        let chunk_length = read_u32_le(glb, offset)
+            .ok_or_else(|| glb_error("GLB chunk header is truncated"))?
+            as usize;
+        let chunk_type = read_u32_le(glb, offset + 4)
+            .ok_or_else(|| glb_error("GLB chunk header is truncated"))?;
+        // Chunks are padded to start and end on a 4-byte boundary
+        if chunk_length % 4 != 0 {
+            return Err(ModelError::InvalidGlb(format!(
+                "GLB chunk length {} is not a multiple of 4",
+                chunk_length
+            )));
+        }
+        let start = offset + 8;
+        let end = start
+            .checked_add(chunk_length)
+            .filter(|end| *end <= glb.len())
+            .ok_or_else(|| glb_error("GLB chunk length exceeds the file length"))?;
 
-        for i in (beginning_of_data..beginning_of_data + length_of_data).step_by(4) {
-            let bytes: [u8; 4] = [
-                self.data[i],
-                self.data[i + 1],
-                self.data[i + 2],
-                self.data[i + 3],
-            ];
-            let value: f32;
-            unsafe {
-                value = std::mem::transmute(bytes);
+        match chunk_type {
+            GLB_CHUNK_JSON if json_chunk.is_none() => json_chunk = Some(&glb[start..end]),
+            GLB_CHUNK_JSON => return Err(glb_error("GLB contains more than one JSON chunk")),
+            GLB_CHUNK_BIN if json_chunk.is_none() => {
+                return Err(glb_error("GLB BIN chunk appears before the JSON chunk"))
             }
-            float_vec.push(value);
+            GLB_CHUNK_BIN if bin_chunk.is_empty() => bin_chunk = &glb[start..end],
+            // Unknown chunk types must be ignored per the glTF 2.0 specification
+            _ => {}
         }
 
-        float_vec
+        offset = end;
     }
 
-    fn get_indices(&mut self, accessor: JsonValue) -> Result<Vec<u32>, Box<dyn Error>> {
-        let buff_view_ind = if accessor["bufferView"].is_null() {
-            0
-        } else {
-            accessor["bufferView"].as_u64().unwrap() as usize
-        };
-        let count = accessor["count"].as_u64().unwrap() as usize;
-        let acc_byte_offset = if accessor["byteOffset"].is_null() {
-            0
-        } else {
-            accessor["byteOffset"].as_u64().unwrap() as usize
-        };
-        let component_type = accessor["componentType"].as_u64().unwrap() as u32;
+    let json_chunk = json_chunk.ok_or_else(|| glb_error("GLB does not contain a JSON chunk"))?;
+
+    Ok((json_chunk, bin_chunk))
+}
 
-        let buffer_view = &self.json["bufferViews"][buff_view_ind];
-        let byte_offset = buffer_view["byteOffset"].as_u64().unwrap() as usize;
+fn glb_error(reason: &str) -> ModelError {
+    ModelError::InvalidGlb(reason.to_string())
+}
+
+fn interpret_sampler(sampler: &JsonValue) -> texture::Sampler {
+    let default = texture::Sampler::default();
+
+    texture::Sampler {
+        mag_filter: sampler["magFilter"].as_u32().unwrap_or(default.mag_filter),
+        min_filter: sampler["minFilter"].as_u32().unwrap_or(default.min_filter),
+        wrap_s: sampler["wrapS"].as_u32().unwrap_or(default.wrap_s),
+        wrap_t: sampler["wrapT"].as_u32().unwrap_or(default.wrap_t),
+    }
+}
+
+/// Maps a glTF primitive `mode` onto the GL draw mode of the same meaning.
+fn interpret_mode(mode: u32) -> Result<gl::types::GLenum, &'static str> {
+    match mode {
+        0 => Ok(gl::POINTS),
+        1 => Ok(gl::LINES),
+        2 => Ok(gl::LINE_LOOP),
+        3 => Ok(gl::LINE_STRIP),
+        4 => Ok(gl::TRIANGLES),
+        5 => Ok(gl::TRIANGLE_STRIP),
+        6 => Ok(gl::TRIANGLE_FAN),
+        _ => Err("Mode is invalid (not between 0 and 6)"),
+    }
+}
 
-        let beginning_of_data = byte_offset + acc_byte_offset;
-        let mut indices = Vec::with_capacity(count);
+/// Returns the (columns, rows) of an accessor type.
+fn interpret_type(type_str: &str) -> Result<(usize, usize), &'static str> {
+    match type_str {
+        "SCALAR" => Ok((1, 1)),
+        "VEC2" => Ok((1, 2)),
+        "VEC3" => Ok((1, 3)),
+        "VEC4" => Ok((1, 4)),
+        "MAT2" => Ok((2, 2)),
+        "MAT3" => Ok((3, 3)),
+        "MAT4" => Ok((4, 4)),
+        _ => Err("Type is invalid (not SCALAR, VECn, or MATn)"),
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ComponentType {
+    Byte,
+    UnsignedByte,
+    Short,
+    UnsignedShort,
+    UnsignedInt,
+    Float,
+}
 
+impl ComponentType {
+    fn from_gl(component_type: u32) -> Option<ComponentType> {
         match component_type {
-            5125 => {
-                for i in (beginning_of_data..beginning_of_data + count * 4).step_by(4) {
-                    let bytes: [u8; 4] = [
-                        self.data[i],
-                        self.data[i + 1],
-                        self.data[i + 2],
-                        self.data[i + 3],
-                    ];
-                    let value: u32;
-                    unsafe {
-                        value = std::mem::transmute(bytes);
-                    }
-                    indices.push(value);
-                }
+            5120 => Some(ComponentType::Byte),
+            5121 => Some(ComponentType::UnsignedByte),
+            5122 => Some(ComponentType::Short),
+            5123 => Some(ComponentType::UnsignedShort),
+            5125 => Some(ComponentType::UnsignedInt),
+            5126 => Some(ComponentType::Float),
+            _ => None,
+        }
+    }
+
+    fn size(self) -> usize {
+        match self {
+            ComponentType::Byte | ComponentType::UnsignedByte => 1,
+            ComponentType::Short | ComponentType::UnsignedShort => 2,
+            ComponentType::UnsignedInt | ComponentType::Float => 4,
+        }
+    }
+
+    /// Reads one component. Normalized integers map onto [0, 1] (unsigned) or [-1, 1] (signed).
+    fn read_f32(self, b: &[u8], normalized: bool) -> f32 {
+        match (self, normalized) {
+            (ComponentType::Float, _) => f32::from_le_bytes([b[0], b[1], b[2], b[3]]),
+            (ComponentType::Byte, true) => (b[0] as i8 as f32 / 127.0).max(-1.0),
+            (ComponentType::Byte, false) => b[0] as i8 as f32,
+            (ComponentType::UnsignedByte, true) => b[0] as f32 / 255.0,
+            (ComponentType::UnsignedByte, false) => b[0] as f32,
+            (ComponentType::Short, true) => {
+                (i16::from_le_bytes([b[0], b[1]]) as f32 / 32767.0).max(-1.0)
             }
-            5123 => {
-                for i in (beginning_of_data..beginning_of_data + count * 2).step_by(2) {
-                    let bytes: [u8; 2] = [self.data[i], self.data[i + 1]];
-                    let value: u16;
-                    unsafe {
-                        value = std::mem::transmute(bytes);
-                    }
-                    indices.push(value as u32);
-                }
+            (ComponentType::Short, false) => i16::from_le_bytes([b[0], b[1]]) as f32,
+            (ComponentType::UnsignedShort, true) => {
+                u16::from_le_bytes([b[0], b[1]]) as f32 / 65535.0
             }
-            5122 => {
-                for i in (beginning_of_data..beginning_of_data + count * 2).step_by(2) {
-                    let bytes: [u8; 2] = [self.data[i], self.data[i + 1]];
-                    let value: i16;
-                    unsafe {
-                        value = std::mem::transmute(bytes);
-                    }
-                    indices.push(value as u32);
-                }
+            (ComponentType::UnsignedShort, false) => u16::from_le_bytes([b[0], b[1]]) as f32,
+            (ComponentType::UnsignedInt, true) => {
+                (u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64 / u32::MAX as f64) as f32
+            }
+            (ComponentType::UnsignedInt, false) => {
+                u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f32
             }
-            _ => return Err("Invalid componentType for indices".into()),
         }
+    }
 
-        Ok(indices)
+    fn read_u32(self, b: &[u8]) -> u32 {
+        match self {
+            ComponentType::Byte => b[0] as i8 as u32,
+            ComponentType::UnsignedByte => b[0] as u32,
+            ComponentType::Short => i16::from_le_bytes([b[0], b[1]]) as u32,
+            ComponentType::UnsignedShort => u16::from_le_bytes([b[0], b[1]]) as u32,
+            ComponentType::UnsignedInt => u32::from_le_bytes([b[0], b[1], b[2], b[3]]),
+            ComponentType::Float => f32::from_le_bytes([b[0], b[1], b[2], b[3]]) as u32,
+        }
     }
+}
 
-    unsafe fn load_mesh(&mut self, ind_mesh: usize) {
-        let pos_acc_ind = self.json["meshes"][ind_mesh]["primitives"][0]["attributes"]["POSITION"]
-            .as_u64()
-            .expect("Expected a u64 value in JSON") as usize;
-        let normal_acc_ind = self.json["meshes"][ind_mesh]["primitives"][0]["attributes"]["NORMAL"]
-            .as_u64()
-            .expect("Expected a u64 value in JSON") as usize;
-
-        let tex_acc_ind = self.json["meshes"][ind_mesh]["primitives"][0]["attributes"]["TEXCOORD_0"]
-            .as_u64()
-            .expect("Expected a u64 value in JSON") as usize;
-
-        let ind_acc_ind = self.json["meshes"][ind_mesh]["primitives"][0]["indices"]
-            .as_u64()
-            .expect("Expected a u64 value in JSON") as usize;
-
-        let pos_vec = ReadyModel::get_floats(self, self.json["accessors"][pos_acc_ind].clone());
-        let positions = group_floats_vec3(pos_vec);
-        let normal_vec =
-            ReadyModel::get_floats(self, self.json["accessors"][normal_acc_ind].clone());
-        let normals = group_floats_vec3(normal_vec);
-        let tex_vec = ReadyModel::get_floats(self, self.json["accessors"][tex_acc_ind].clone());
-        let tex_uvs = group_floats_vec2(tex_vec);
-
-        let vertices = assemble_vertices(positions, normals, tex_uvs);
-        let indices = self
-            .get_indices(self.json["accessors"][ind_acc_ind].clone())
-            .unwrap();
-        let texture = texture::Texture::new(self.gl.clone(), self.texture_file);
+/// Reads an accessor as floats, applying `normalized`. Returns the values
+/// flattened (matrices column by column) along with the components per element.
+fn read_accessor_f32(
+    json: &JsonValue,
+    buffers: &[Vec<u8>],
+    ind_accessor: usize,
+) -> Result<(Vec<f32>, usize), ModelError> {
+    read_accessor(
+        json,
+        buffers,
+        ind_accessor,
+        |component_type, normalized, bytes| component_type.read_f32(bytes, normalized),
+    )
+}
 
-        self.meshes
-            .push(mesh::Mesh::new(self.gl.clone(), vertices, indices, texture));
+/// Reads an integer accessor (indices, joints) without normalization.
+fn read_accessor_u32(
+    json: &JsonValue,
+    buffers: &[Vec<u8>],
+    ind_accessor: usize,
+) -> Result<(Vec<u32>, usize), ModelError> {
+    let component_type = json["accessors"][ind_accessor]["componentType"]
+        .as_u32()
+        .unwrap_or(0);
+    if component_type == 5126 {
+        return Err(ModelError::UnsupportedComponentType {
+            accessor: ind_accessor,
+            component_type,
+        });
     }
 
-    unsafe fn traverse_node(
-        state: &mut TraverseState,
-        json: JsonValue,
-        next_node: usize,
-        matrix: nalgebra_glm::Mat4,
-    ) {
-        let node = &json["nodes"][next_node];
+    read_accessor(json, buffers, ind_accessor, |component_type, _, bytes| {
+        component_type.read_u32(bytes)
+    })
+}
+
+fn read_accessor<T: Copy + Default>(
+    json: &JsonValue,
+    buffers: &[Vec<u8>],
+    ind_accessor: usize,
+    read: impl Fn(ComponentType, bool, &[u8]) -> T,
+) -> Result<(Vec<T>, usize), ModelError> {
+    let out_of_bounds = || ModelError::AccessorOutOfBounds {
+        accessor: ind_accessor,
+    };
+
+    let accessor = &json["accessors"][ind_accessor];
+    if !accessor.is_object() {
+        return Err(ModelError::MissingField(format!(
+            "accessors[{}]",
+            ind_accessor
+        )));
+    }
+
+    let count = require(accessor["count"].as_usize(), || {
+        format!("accessors[{}].count", ind_accessor)
+    })?;
+    let type_str = require(accessor["type"].as_str(), || {
+        format!("accessors[{}].type", ind_accessor)
+    })?;
+    let (columns, rows) = interpret_type(type_str).map_err(|reason| {
+        ModelError::InvalidJson(format!("accessors[{}]: {}", ind_accessor, reason))
+    })?;
+    let component_value = require(accessor["componentType"].as_u32(), || {
+        format!("accessors[{}].componentType", ind_accessor)
+    })?;
+    let component_type =
+        ComponentType::from_gl(component_value).ok_or(ModelError::UnsupportedComponentType {
+            accessor: ind_accessor,
+            component_type: component_value,
+        })?;
+    let normalized = accessor["normalized"].as_bool().unwrap_or(false);
+
+    let size = component_type.size();
+    // Matrix columns always start on a 4-byte boundary
+    let column_stride = if columns > 1 {
+        (rows * size + 3) & !3
+    } else {
+        rows * size
+    };
+    let element_size = column_stride * columns;
+    let components = columns * rows;
 
-        let mut translation: nalgebra_glm::Vec3 = nalgebra_glm::vec3(0.0, 0.0, 0.0);
-        if !node["translation"].is_null() {
-            let mut trans_values: [f32; 3] = [0.0; 3];
-            for i in 0..node["translation"].len() {
-                trans_values[i] = node["translation"][i].as_f32().unwrap();
+    let read_element = |bytes: &[u8], values: &mut [T]| {
+        for column in 0..columns {
+            for row in 0..rows {
+                let at = column * column_stride + row * size;
+                values[column * rows + row] =
+                    read(component_type, normalized, &bytes[at..at + size]);
             }
-            translation = nalgebra_glm::make_vec3(&trans_values);
         }
+    };
 
-        let mut rotation: nalgebra_glm::Quat = nalgebra_glm::quat(1.0, 0.0, 0.0, 0.0);
-        if !node["rotation"].is_null() {
-            let rotation_values: [f32; 4] = [
-                node["rotation"][3].as_f32().unwrap(),
-                node["rotation"][0].as_f32().unwrap(),
-                node["rotation"][1].as_f32().unwrap(),
-                node["rotation"][2].as_f32().unwrap(),
-            ];
-            rotation = nalgebra_glm::make_quat(&rotation_values)
-        }
+    let total = count.checked_mul(components).ok_or_else(out_of_bounds)?;
+    // Accessors without a bufferView are all zeros, usually overridden by sparse values
+    let mut values = vec![T::default(); total];
+
+    if let Some(ind_view) = accessor["bufferView"].as_usize() {
+        let (view, byte_stride) = buffer_view_bytes(json, buffers, ind_view, ind_accessor)?;
+        let stride = byte_stride.unwrap_or(element_size);
+        let offset = accessor["byteOffset"].as_usize().unwrap_or(0);
 
-        let mut scale: nalgebra_glm::Vec3 = nalgebra_glm::vec3(1.0, 1.0, 1.0);
-        if !node["scale"].is_null() {
-            let mut scale_values: [f32; 3] = [0.0; 3];
-            for i in 0..node["scale"].len() {
-                scale_values[i] = node["scale"][i].as_f32().unwrap();
+        if count > 0 {
+            let end = stride
+                .checked_mul(count - 1)
+                .and_then(|last| last.checked_add(offset))
+                .and_then(|last| last.checked_add(element_size))
+                .ok_or_else(out_of_bounds)?;
+            if end > view.len() {
+                return Err(out_of_bounds());
             }
-            scale = nalgebra_glm::make_vec3(&scale_values);
         }
 
-        let mut mat_node: nalgebra_glm::Mat4 = nalgebra_glm::Mat4::identity();
-        if !node["matrix"].is_null() {
-            let mut mat_values: [f32; 16] = [0.0; 16];
-            for i in 0..node["matrix"].len() {
-                mat_values[i] = node["matrix"][i].as_f32().unwrap();
-            }
-            mat_node = nalgebra_glm::make_mat4(&mat_values);
+        for element in 0..count {
+            let start = offset + stride * element;
+            read_element(
+                &view[start..start + element_size],
+                &mut values[element * components..(element + 1) * components],
+            );
         }
+    }
 
-        let trans = nalgebra_glm::translate(&nalgebra_glm::Mat4::identity(), &translation);
-        let rot = nalgebra_glm::quat_cast(&rotation);
-        let sca = nalgebra_glm::scale(&nalgebra_glm::Mat4::identity(), &scale);
+    let sparse = &accessor["sparse"];
+    if sparse.is_object() {
+        let path = |field: &str| format!("accessors[{}].sparse.{}", ind_accessor, field);
 
-        let mat_next_node: nalgebra_glm::Mat4 = matrix * mat_node * trans * rot * sca;
+        let sparse_count = require(sparse["count"].as_usize(), || path("count"))?;
+        let indices = &sparse["indices"];
+        let index_value = require(indices["componentType"].as_u32(), || {
+            path("indices.componentType")
+        })?;
+        let index_type = match ComponentType::from_gl(index_value) {
+            Some(
+                index_type @ (ComponentType::UnsignedByte
+                | ComponentType::UnsignedShort
+                | ComponentType::UnsignedInt),
+            ) => index_type,
+            _ => {
+                return Err(ModelError::UnsupportedComponentType {
+                    accessor: ind_accessor,
+                    component_type: index_value,
+                })
+            }
+        };
+
+        let (index_view, _) = buffer_view_bytes(
+            json,
+            buffers,
+            require(indices["bufferView"].as_usize(), || {
+                path("indices.bufferView")
+            })?,
+            ind_accessor,
+        )?;
+        let index_offset = indices["byteOffset"].as_usize().unwrap_or(0);
+        let (value_view, _) = buffer_view_bytes(
+            json,
+            buffers,
+            require(sparse["values"]["bufferView"].as_usize(), || {
+                path("values.bufferView")
+            })?,
+            ind_accessor,
+        )?;
+        let value_offset = sparse["values"]["byteOffset"].as_usize().unwrap_or(0);
 
-        if !node["mesh"].is_null() {
-            state.translations_meshes.push(translation);
-            state.rotations_meshes.push(rotation);
-            state.scales_meshes.push(scale);
-            state
-                .meshes_to_load
-                .push(node["mesh"].as_u64().expect("Expected a u64 value in JSON") as usize);
+        let index_end = sparse_count
+            .checked_mul(index_type.size())
+            .and_then(|len| len.checked_add(index_offset));
+        let value_end = sparse_count
+            .checked_mul(element_size)
+            .and_then(|len| len.checked_add(value_offset));
+        match (index_end, value_end) {
+            (Some(index_end), Some(value_end))
+                if index_end <= index_view.len() && value_end <= value_view.len() => {}
+            _ => return Err(out_of_bounds()),
         }
 
-        if !node["children"].is_null() {
-            for i in 0..node["children"].len() {
-                ReadyModel::traverse_node(
-                    state,
-                    json.clone(),
-                    node["children"][i].as_u64().unwrap() as usize,
-                    mat_next_node,
-                );
+        for substitution in 0..sparse_count {
+            let at = index_offset + substitution * index_type.size();
+            let element = index_type.read_u32(&index_view[at..at + index_type.size()]) as usize;
+            if element >= count {
+                return Err(out_of_bounds());
             }
+
+            let start = value_offset + substitution * element_size;
+            read_element(
+                &value_view[start..start + element_size],
+                &mut values[element * components..(element + 1) * components],
+            );
         }
     }
+
+    Ok((values, components))
 }
 
-fn interpret_type(type_str: &str) -> Result<usize, &'static str> {
-    match type_str {
-        "SCALAR" => Ok(1),
-        "VEC2" => Ok(2),
-        "VEC3" => Ok(3),
-        "VEC4" => Ok(4),
-        _ => Err("Type is invalid (not SCALAR, VEC2, VEC3, or VEC4)"),
+/// Returns the bytes of a bufferView along with its `byteStride`, if any.
+fn buffer_view_bytes<'a>(
+    json: &JsonValue,
+    buffers: &'a [Vec<u8>],
+    ind_view: usize,
+    ind_accessor: usize,
+) -> Result<(&'a [u8], Option<usize>), ModelError> {
+    let buffer_view = &json["bufferViews"][ind_view];
+    let buffer = require(buffer_view["buffer"].as_usize(), || {
+        format!("bufferViews[{}].buffer", ind_view)
+    })?;
+    let byte_offset = buffer_view["byteOffset"].as_usize().unwrap_or(0);
+    let byte_length = require(buffer_view["byteLength"].as_usize(), || {
+        format!("bufferViews[{}].byteLength", ind_view)
+    })?;
+
+    let bytes = byte_offset
+        .checked_add(byte_length)
+        .and_then(|end| buffers.get(buffer)?.get(byte_offset..end))
+        .ok_or(ModelError::AccessorOutOfBounds {
+            accessor: ind_accessor,
+        })?;
+
+    Ok((bytes, buffer_view["byteStride"].as_usize()))
+}
+
+/// Splits the faces of a primitive into triangles. Strips alternate their winding
+/// so every triangle faces the same way, modes without faces yield none.
+fn triangles(mode: gl::types::GLenum, indices: &[u32]) -> Vec<[u32; 3]> {
+    match mode {
+        gl::TRIANGLES => indices
+            .chunks_exact(3)
+            .map(|tri| [tri[0], tri[1], tri[2]])
+            .collect(),
+        gl::TRIANGLE_STRIP => (2..indices.len())
+            .map(|i| match i % 2 {
+                0 => [indices[i - 2], indices[i - 1], indices[i]],
+                _ => [indices[i - 1], indices[i - 2], indices[i]],
+            })
+            .collect(),
+        gl::TRIANGLE_FAN => (2..indices.len())
+            .map(|i| [indices[0], indices[i - 1], indices[i]])
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn face_normal(
+    a: &nalgebra_glm::Vec3,
+    b: &nalgebra_glm::Vec3,
+    c: &nalgebra_glm::Vec3,
+) -> nalgebra_glm::Vec3 {
+    nalgebra_glm::cross(&(b - a), &(c - a))
+}
+
+fn normalize_or_up(normal: &nalgebra_glm::Vec3) -> nalgebra_glm::Vec3 {
+    if nalgebra_glm::length(normal) > f32::EPSILON {
+        nalgebra_glm::normalize(normal)
+    } else {
+        nalgebra_glm::vec3(0.0, 1.0, 0.0)
+    }
+}
+
+/// Averages the normals of the faces around each vertex, weighted by face area.
+fn smooth_normals(
+    positions: &[nalgebra_glm::Vec3],
+    triangles: &[[u32; 3]],
+) -> Vec<nalgebra_glm::Vec3> {
+    let mut normals = vec![nalgebra_glm::vec3(0.0, 0.0, 0.0); positions.len()];
+
+    for [a, b, c] in triangles {
+        let (a, b, c) = (*a as usize, *b as usize, *c as usize);
+        let normal = face_normal(&positions[a], &positions[b], &positions[c]);
+        normals[a] += normal;
+        normals[b] += normal;
+        normals[c] += normal;
+    }
+
+    normals.iter().map(normalize_or_up).collect()
+}
+
+/// Gives every corner of consecutive, unshared triangles its face's normal.
+fn flat_normals(positions: &[nalgebra_glm::Vec3]) -> Vec<nalgebra_glm::Vec3> {
+    positions
+        .chunks_exact(3)
+        .flat_map(|tri| {
+            let normal = normalize_or_up(&face_normal(&tri[0], &tri[1], &tri[2]));
+            [normal; 3]
+        })
+        .collect()
+}
+
+fn unweld<T: Copy>(values: &[T], indices: &[u32]) -> Vec<T> {
+    indices
+        .iter()
+        .map(|index| values[*index as usize])
+        .collect()
+}
+
+/// Scales skin weights to sum to one. Vertices without any weight follow the first joint.
+fn normalize_weights(weights: &nalgebra_glm::Vec4) -> nalgebra_glm::Vec4 {
+    let sum = weights.x + weights.y + weights.z + weights.w;
+    if sum > f32::EPSILON {
+        weights / sum
+    } else {
+        nalgebra_glm::vec4(1.0, 0.0, 0.0, 0.0)
     }
 }
 
@@ -362,7 +2198,6 @@ fn group_floats_vec3(float_vec: Vec<f32>) -> Vec<nalgebra_glm::Vec3> {
     vectors
 }
 
-#[allow(dead_code)]
 fn group_floats_vec4(float_vec: Vec<f32>) -> Vec<nalgebra_glm::Vec4> {
     let mut vectors = Vec::with_capacity(float_vec.len() / 4);
     let mut iter = float_vec.iter();
@@ -379,14 +2214,20 @@ fn group_floats_vec4(float_vec: Vec<f32>) -> Vec<nalgebra_glm::Vec4> {
 fn assemble_vertices(
     positions: Vec<nalgebra_glm::Vec3>,
     normals: Vec<nalgebra_glm::Vec3>,
+    colors: Vec<nalgebra_glm::Vec4>,
     tex_uvs: Vec<nalgebra_glm::Vec2>,
+    joints: Vec<nalgebra_glm::Vec4>,
+    weights: Vec<nalgebra_glm::Vec4>,
 ) -> Vec<f32> {
-    let mut vertices = Vec::new();
+    let mut vertices = Vec::with_capacity(positions.len() * mesh::VERTEX_SIZE);
 
     for i in 0..positions.len() {
         let position = positions[i];
         let normal = normals[i];
+        let color = colors[i];
         let tex_uv = tex_uvs[i];
+        let joint = joints[i];
+        let weight = weights[i];
 
         vertices.push(position.x);
         vertices.push(position.y);
@@ -396,13 +2237,576 @@ fn assemble_vertices(
         vertices.push(normal.y);
         vertices.push(normal.z);
 
-        vertices.push(1.0);
-        vertices.push(1.0);
-        vertices.push(1.0);
+        vertices.push(color.x);
+        vertices.push(color.y);
+        vertices.push(color.z);
+        vertices.push(color.w);
 
         vertices.push(tex_uv.x);
         vertices.push(tex_uv.y);
+
+        vertices.extend_from_slice(joint.as_slice());
+        vertices.extend_from_slice(weight.as_slice());
     }
 
     vertices
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn f32_bytes(values: &[f32]) -> Vec<u8> {
+        values.iter().flat_map(|v| v.to_le_bytes()).collect()
+    }
+
+    #[test]
+    fn generates_smooth_normals_from_strips() {
+        // A quad as a strip, lying in the XZ plane and facing up
+        let positions = [
+            nalgebra_glm::vec3(0.0, 0.0, 0.0),
+            nalgebra_glm::vec3(0.0, 0.0, 1.0),
+            nalgebra_glm::vec3(1.0, 0.0, 0.0),
+            nalgebra_glm::vec3(1.0, 0.0, 1.0),
+        ];
+        let triangles = triangles(gl::TRIANGLE_STRIP, &[0, 1, 2, 3]);
+        assert_eq!(triangles, vec![[0, 1, 2], [2, 1, 3]]);
+
+        for normal in smooth_normals(&positions, &triangles) {
+            assert_eq!(normal, nalgebra_glm::vec3(0.0, 1.0, 0.0));
+        }
+    }
+
+    #[test]
+    fn generates_flat_normals_per_face() {
+        // Two triangles folded along the Z axis, one facing +Y and one facing +X
+        let positions = [
+            nalgebra_glm::vec3(0.0, 0.0, 0.0),
+            nalgebra_glm::vec3(0.0, 0.0, 1.0),
+            nalgebra_glm::vec3(1.0, 0.0, 0.0),
+            nalgebra_glm::vec3(0.0, 1.0, 0.0),
+        ];
+        let corners: Vec<u32> = triangles(gl::TRIANGLES, &[0, 1, 2, 0, 3, 1]).concat();
+        let normals = flat_normals(&unweld(&positions, &corners));
+
+        assert_eq!(normals.len(), 6);
+        assert_eq!(&normals[..3], &[nalgebra_glm::vec3(0.0, 1.0, 0.0); 3]);
+        assert_eq!(&normals[3..], &[nalgebra_glm::vec3(1.0, 0.0, 0.0); 3]);
+    }
+
+    #[test]
+    fn reads_tightly_packed_floats() {
+        let data = f32_bytes(&[1.0, 2.0, 3.0, 4.0, 5.0, 6.0]);
+        let json = json::parse(
+            r#"{
+                "bufferViews": [{ "buffer": 0, "byteLength": 24 }],
+                "accessors": [{ "bufferView": 0, "componentType": 5126, "count": 2, "type": "VEC3" }]
+            }"#,
+        )
+        .unwrap();
+
+        let buffers = [data];
+        let (values, components) = read_accessor_f32(&json, &buffers, 0).unwrap();
+        assert_eq!(components, 3);
+        assert_eq!(values, vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0]);
+    }
+
+    #[test]
+    fn reads_interleaved_attributes_with_byte_stride() {
+        // Two vertices of [position: VEC3 f32][uv: VEC2 u16 normalized][2 bytes padding]
+        let mut data = Vec::new();
+        for (position, uv) in [
+            ([1.0, 2.0, 3.0], [0u16, 65535]),
+            ([4.0, 5.0, 6.0], [65535, 0]),
+        ] {
+            data.extend(f32_bytes(&position));
+            data.extend(uv.iter().flat_map(|v| v.to_le_bytes()));
+            data.extend([0, 0]);
+        }
+        let json = json::parse(
+            r#"{
+                "bufferViews": [{ "buffer": 0, "byteLength": 36, "byteStride": 18 }],
+                "accessors": [
+                    { "bufferView": 0, "componentType": 5126, "count": 2, "type": "VEC3" },
+                    { "bufferView": 0, "byteOffset": 12, "componentType": 5123, "normalized": true, "count": 2, "type": "VEC2" }
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        let buffers = [data];
+        let (positions, _) = read_accessor_f32(&json, &buffers, 0).unwrap();
+        assert_eq!(positions, vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0]);
+        let (uvs, _) = read_accessor_f32(&json, &buffers, 1).unwrap();
+        assert_eq!(uvs, vec![0.0, 1.0, 1.0, 0.0]);
+    }
+
+    #[test]
+    fn normalizes_signed_and_unsigned_bytes() {
+        let data = vec![0, 255, 51, 0, 127, 0x81, 0x80, 0];
+        let json = json::parse(
+            r#"{
+                "bufferViews": [{ "buffer": 0, "byteLength": 8 }],
+                "accessors": [
+                    { "bufferView": 0, "componentType": 5121, "normalized": true, "count": 3, "type": "SCALAR" },
+                    { "bufferView": 0, "byteOffset": 4, "componentType": 5120, "normalized": true, "count": 4, "type": "SCALAR" },
+                    { "bufferView": 0, "byteOffset": 4, "componentType": 5120, "count": 4, "type": "SCALAR" }
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        let buffers = [data];
+        let (unsigned, _) = read_accessor_f32(&json, &buffers, 0).unwrap();
+        assert_eq!(unsigned, vec![0.0, 1.0, 0.2]);
+        let (signed, _) = read_accessor_f32(&json, &buffers, 1).unwrap();
+        assert_eq!(signed, vec![1.0, -1.0, -1.0, 0.0]);
+        let (raw, _) = read_accessor_f32(&json, &buffers, 2).unwrap();
+        assert_eq!(raw, vec![127.0, -127.0, -128.0, 0.0]);
+    }
+
+    #[test]
+    fn pads_matrix_columns_to_four_bytes() {
+        // MAT2 of unsigned bytes: each 2-byte column is padded to 4 bytes
+        let data = vec![1, 2, 0, 0, 3, 4, 0, 0];
+        let json = json::parse(
+            r#"{
+                "bufferViews": [{ "buffer": 0, "byteLength": 8 }],
+                "accessors": [{ "bufferView": 0, "componentType": 5121, "count": 1, "type": "MAT2" }]
+            }"#,
+        )
+        .unwrap();
+
+        let buffers = [data];
+        let (values, components) = read_accessor_f32(&json, &buffers, 0).unwrap();
+        assert_eq!(components, 4);
+        assert_eq!(values, vec![1.0, 2.0, 3.0, 4.0]);
+    }
+
+    #[test]
+    fn substitutes_sparse_values_into_zeroed_accessor() {
+        // Sparse indices [1, 3] as u16, then replacement VEC2 floats
+        let mut data: Vec<u8> = [1u16, 3].iter().flat_map(|v| v.to_le_bytes()).collect();
+        data.extend(f32_bytes(&[7.0, 8.0, 9.0, 10.0]));
+        let json = json::parse(
+            r#"{
+                "bufferViews": [
+                    { "buffer": 0, "byteLength": 4 },
+                    { "buffer": 0, "byteOffset": 4, "byteLength": 16 }
+                ],
+                "accessors": [{
+                    "componentType": 5126, "count": 4, "type": "VEC2",
+                    "sparse": {
+                        "count": 2,
+                        "indices": { "bufferView": 0, "componentType": 5123 },
+                        "values": { "bufferView": 1 }
+                    }
+                }]
+            }"#,
+        )
+        .unwrap();
+
+        let buffers = [data];
+        let (values, _) = read_accessor_f32(&json, &buffers, 0).unwrap();
+        assert_eq!(values, vec![0.0, 0.0, 7.0, 8.0, 0.0, 0.0, 9.0, 10.0]);
+    }
+
+    #[test]
+    fn substitutes_sparse_values_over_buffer_data() {
+        let mut data = f32_bytes(&[1.0, 2.0, 3.0]);
+        data.push(2);
+        data.extend([0, 0, 0]);
+        data.extend(f32_bytes(&[30.0]));
+        let json = json::parse(
+            r#"{
+                "bufferViews": [
+                    { "buffer": 0, "byteLength": 12 },
+                    { "buffer": 0, "byteOffset": 12, "byteLength": 1 },
+                    { "buffer": 0, "byteOffset": 16, "byteLength": 4 }
+                ],
+                "accessors": [{
+                    "bufferView": 0, "componentType": 5126, "count": 3, "type": "SCALAR",
+                    "sparse": {
+                        "count": 1,
+                        "indices": { "bufferView": 1, "componentType": 5121 },
+                        "values": { "bufferView": 2 }
+                    }
+                }]
+            }"#,
+        )
+        .unwrap();
+
+        let buffers = [data];
+        let (values, _) = read_accessor_f32(&json, &buffers, 0).unwrap();
+        assert_eq!(values, vec![1.0, 2.0, 30.0]);
+    }
+
+    #[test]
+    fn reads_indices_of_every_unsigned_type() {
+        let mut data = vec![0, 1, 2, 0];
+        data.extend([3u16, 4].iter().flat_map(|v| v.to_le_bytes()));
+        data.extend(70000u32.to_le_bytes());
+        let json = json::parse(
+            r#"{
+                "bufferViews": [{ "buffer": 0, "byteLength": 12 }],
+                "accessors": [
+                    { "bufferView": 0, "componentType": 5121, "count": 3, "type": "SCALAR" },
+                    { "bufferView": 0, "byteOffset": 4, "componentType": 5123, "count": 2, "type": "SCALAR" },
+                    { "bufferView": 0, "byteOffset": 8, "componentType": 5125, "count": 1, "type": "SCALAR" },
+                    { "bufferView": 0, "componentType": 5126, "count": 1, "type": "SCALAR" }
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        let buffers = [data];
+        assert_eq!(
+            read_accessor_u32(&json, &buffers, 0).unwrap().0,
+            vec![0, 1, 2]
+        );
+        assert_eq!(read_accessor_u32(&json, &buffers, 1).unwrap().0, vec![3, 4]);
+        assert_eq!(
+            read_accessor_u32(&json, &buffers, 2).unwrap().0,
+            vec![70000]
+        );
+        assert!(matches!(
+            read_accessor_u32(&json, &buffers, 3),
+            Err(ModelError::UnsupportedComponentType {
+                accessor: 3,
+                component_type: 5126
+            })
+        ));
+    }
+
+    #[test]
+    fn rejects_accessors_past_the_end_of_their_view() {
+        let data = f32_bytes(&[1.0, 2.0, 3.0]);
+        let json = json::parse(
+            r#"{
+                "bufferViews": [
+                    { "buffer": 0, "byteLength": 12 },
+                    { "buffer": 0, "byteLength": 64 }
+                ],
+                "accessors": [
+                    { "bufferView": 0, "componentType": 5126, "count": 2, "type": "VEC3" },
+                    { "bufferView": 1, "componentType": 5126, "count": 1, "type": "SCALAR" },
+                    { "bufferView": 0, "componentType": 5124, "count": 1, "type": "SCALAR" }
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        let buffers = [data];
+        assert!(matches!(
+            read_accessor_f32(&json, &buffers, 0),
+            Err(ModelError::AccessorOutOfBounds { accessor: 0 })
+        ));
+        assert!(matches!(
+            read_accessor_f32(&json, &buffers, 1),
+            Err(ModelError::AccessorOutOfBounds { accessor: 1 })
+        ));
+        assert!(matches!(
+            read_accessor_f32(&json, &buffers, 2),
+            Err(ModelError::UnsupportedComponentType {
+                accessor: 2,
+                component_type: 5124
+            })
+        ));
+
+        // Images read their bufferView without an accessor
+        for view in [
+            r#"{ "buffer": 0, "byteOffset": 32, "byteLength": 16 }"#,
+            r#"{ "buffer": 3, "byteLength": 4 }"#,
+        ] {
+            let gltf = format!(
+                r#"{{
+                    "asset": {{ "version": "2.0" }},
+                    "nodes": [{{ "mesh": 0 }}],
+                    "meshes": [{{ "primitives": [{{ "attributes": {{ "POSITION": 0 }}, "material": 0 }}] }}],
+                    "materials": [{{ "pbrMetallicRoughness": {{ "baseColorTexture": {{ "index": 0 }} }} }}],
+                    "textures": [{{ "source": 0 }}],
+                    "images": [{{ "bufferView": 1, "mimeType": "image/png" }}],
+                    "accessors": [{{ "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3" }}],
+                    "bufferViews": [{{ "buffer": 0, "byteLength": 36 }}, {}],
+                    "buffers": [{{ "byteLength": 36 }}]
+                }}"#,
+                view
+            );
+            let model = Model::from_bytes(gltf.into_bytes()).unwrap();
+            let model = Model {
+                bin_file: Cow::Owned(f32_bytes(&[0.0; 9])),
+                ..model
+            };
+            assert!(
+                matches!(model.load(), Err(ModelError::InvalidJson(_))),
+                "{}",
+                view
+            );
+        }
+    }
+
+    #[test]
+    fn orders_parents_before_children() {
+        let json = json::parse(
+            r#"{
+                "nodes": [
+                    { "children": [2], "translation": [1, 0, 0] },
+                    { "children": [0] },
+                    { "translation": [0, 2, 0] }
+                ]
+            }"#,
+        )
+        .unwrap();
+        let nodes: Vec<Node> = (0..3).map(|i| parse_node(&json, i).unwrap()).collect();
+
+        let mut order = Vec::new();
+        for root in root_nodes(&json) {
+            traverse_node(&mut order, &nodes, root, None, 0).unwrap();
+        }
+        assert_eq!(order, vec![(1, None), (0, Some(1)), (2, Some(0))]);
+    }
+
+    #[test]
+    fn loads_inverse_bind_matrices_of_a_skin() {
+        let mut matrices = Vec::new();
+        for joint in 0..2 {
+            let matrix = nalgebra_glm::translation(&nalgebra_glm::vec3(0.0, -(joint as f32), 0.0));
+            matrices.extend_from_slice(matrix.as_slice());
+        }
+        let data = f32_bytes(&matrices);
+        let json = json::parse(
+            r#"{
+                "nodes": [{}, {}],
+                "skins": [{ "joints": [0, 1], "inverseBindMatrices": 0 }],
+                "bufferViews": [{ "buffer": 0, "byteLength": 128 }],
+                "accessors": [{ "bufferView": 0, "componentType": 5126, "count": 2, "type": "MAT4" }]
+            }"#,
+        )
+        .unwrap();
+
+        let buffers = [data];
+        let skin = load_skin(&json, &buffers, 2, 0).unwrap();
+        assert_eq!(skin.joints, vec![0, 1]);
+        assert_eq!(skin.inverse_bind_matrices[1][(1, 3)], -1.0);
+    }
+
+    #[test]
+    fn rejects_skins_beyond_the_joint_palette() {
+        let joints: Vec<String> = (0..=mesh::MAX_JOINTS).map(|_| "0".to_string()).collect();
+        let json = json::parse(&format!(
+            r#"{{ "nodes": [{{}}], "skins": [{{ "joints": [{}] }}] }}"#,
+            joints.join(", ")
+        ))
+        .unwrap();
+
+        assert!(matches!(
+            load_skin(&json, &[], 1, 0),
+            Err(ModelError::TooManyJoints { skin: 0, .. })
+        ));
+    }
+
+    #[test]
+    fn normalizes_skin_weights() {
+        let weights = normalize_weights(&nalgebra_glm::vec4(2.0, 2.0, 0.0, 0.0));
+        assert_eq!(weights, nalgebra_glm::vec4(0.5, 0.5, 0.0, 0.0));
+
+        let weights = normalize_weights(&nalgebra_glm::vec4(0.0, 0.0, 0.0, 0.0));
+        assert_eq!(weights, nalgebra_glm::vec4(1.0, 0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn groups_weight_keyframes_by_morph_target() {
+        // Two keyframes for a mesh with two morph targets
+        let data = f32_bytes(&[0.0, 1.0, 0.0, 1.0, 1.0, 0.0]);
+        let json = json::parse(
+            r#"{
+                "nodes": [{ "mesh": 0 }],
+                "meshes": [{ "primitives": [{ "attributes": {}, "targets": [{}, {}] }] }],
+                "animations": [{
+                    "channels": [{ "sampler": 0, "target": { "node": 0, "path": "weights" } }],
+                    "samplers": [{ "input": 0, "output": 1 }]
+                }],
+                "bufferViews": [
+                    { "buffer": 0, "byteLength": 8 },
+                    { "buffer": 0, "byteOffset": 8, "byteLength": 16 }
+                ],
+                "accessors": [
+                    { "bufferView": 0, "componentType": 5126, "count": 2, "type": "SCALAR" },
+                    { "bufferView": 1, "componentType": 5126, "count": 4, "type": "SCALAR" }
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        let buffers = [data];
+        let clip = load_animation(&json, &buffers, 1, 0).unwrap();
+        let channel = &clip.channels[0];
+        assert_eq!(channel.path, animation::ChannelPath::Weights);
+        assert_eq!(channel.sampler.components, 2);
+        assert_eq!(channel.sampler.sample(0.5, false), vec![0.5, 0.5]);
+    }
+
+    #[test]
+    fn resolves_buffers_from_bin_data_uris_and_resolver() {
+        let json = json::parse(
+            r#"{
+                "buffers": [
+                    { "uri": "scene.bin", "byteLength": 2 },
+                    { "uri": "data:application/octet-stream;base64,AQIDBA==", "byteLength": 4 },
+                    { "uri": "extra%20data.bin", "byteLength": 1 }
+                ]
+            }"#,
+        )
+        .unwrap();
+        let resolver = resolver::MemoryResolver::new().add_file("extra%20data.bin", vec![9]);
+
+        let buffers = load_buffers(&json, &[7, 8], Some(&resolver)).unwrap();
+        assert_eq!(buffers, vec![vec![7, 8], vec![1, 2, 3, 4], vec![9]]);
+
+        assert!(matches!(
+            load_buffers(&json, &[7, 8], None),
+            Err(ModelError::UnresolvedUri { .. })
+        ));
+    }
+
+    #[test]
+    fn loads_owned_gltf_and_glb_bytes() {
+        let gltf = br#"{ "asset": { "version": "2.0" } }"#.to_vec();
+        let model = Model::from_bytes(gltf.clone()).unwrap();
+        assert_eq!(model.gltf_file.as_ref(), gltf.as_slice());
+        assert!(model.bin_file.is_empty());
+
+        let mut glb = Vec::new();
+        glb.extend(GLB_MAGIC.to_le_bytes());
+        glb.extend(2u32.to_le_bytes());
+        glb.extend(68u32.to_le_bytes());
+        glb.extend(36u32.to_le_bytes());
+        glb.extend(GLB_CHUNK_JSON.to_le_bytes());
+        glb.extend_from_slice(gltf.as_slice());
+        glb.extend([b' '; 3]);
+        glb.extend(4u32.to_le_bytes());
+        glb.extend(GLB_CHUNK_BIN.to_le_bytes());
+        glb.extend([1, 2, 3, 4]);
+
+        let model = Model::from_bytes(glb).unwrap();
+        assert_eq!(model.bin_file.as_ref(), &[1, 2, 3, 4]);
+
+        assert!(matches!(
+            Model::from_path("does/not/exist.gltf"),
+            Err(ModelError::Io { .. })
+        ));
+    }
+
+    /// A GLB with these chunks, each a type and its contents.
+    fn glb_with_chunks(chunks: &[(u32, &[u8])]) -> Vec<u8> {
+        let mut glb = Vec::new();
+        glb.extend(GLB_MAGIC.to_le_bytes());
+        glb.extend(2u32.to_le_bytes());
+        glb.extend(0u32.to_le_bytes());
+        for (kind, data) in chunks {
+            glb.extend((data.len() as u32).to_le_bytes());
+            glb.extend(kind.to_le_bytes());
+            glb.extend_from_slice(data);
+        }
+        let length = glb.len() as u32;
+        glb[8..12].copy_from_slice(&length.to_le_bytes());
+        glb
+    }
+
+    #[test]
+    fn rejects_malformed_glb_files() {
+        let json: &[u8] = br#"{ "asset": { "version": "2.0" } }   "#;
+        let valid = glb_with_chunks(&[(GLB_CHUNK_JSON, json), (GLB_CHUNK_BIN, &[1, 2, 3, 4])]);
+        assert!(parse_glb(&valid).is_ok());
+        let invalid = |glb: &[u8]| matches!(parse_glb(glb), Err(ModelError::InvalidGlb(_)));
+
+        // Header cut short
+        assert!(invalid(&valid[..8]));
+
+        // Chunk running past the end of the file
+        let mut past_end = valid.clone();
+        past_end[12..16].copy_from_slice(&400u32.to_le_bytes());
+        assert!(invalid(&past_end));
+
+        // Declared length off in either direction
+        let mut too_long = valid.clone();
+        too_long[8..12].copy_from_slice(&(valid.len() as u32 + 4).to_le_bytes());
+        assert!(invalid(&too_long));
+        let mut too_short = valid.clone();
+        too_short[8..12].copy_from_slice(&(valid.len() as u32 - 8).to_le_bytes());
+        assert!(invalid(&too_short));
+
+        assert!(invalid(&glb_with_chunks(&[
+            (GLB_CHUNK_BIN, &[1, 2, 3, 4]),
+            (GLB_CHUNK_JSON, json),
+        ])));
+
+        // JSON without its padding
+        assert!(invalid(&glb_with_chunks(&[(
+            GLB_CHUNK_JSON,
+            br#"{ "asset": { "version": "2.0" } }"#
+        )])));
+    }
+
+    #[test]
+    fn loads_model_data_on_another_thread() {
+        let gltf = br#"{
+            "asset": { "version": "2.0" },
+            "scene": 0,
+            "scenes": [{ "nodes": [0] }],
+            "nodes": [{ "mesh": 0 }],
+            "meshes": [{ "primitives": [{ "attributes": { "POSITION": 0 } }] }],
+            "accessors": [{ "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3" }],
+            "bufferViews": [{ "buffer": 0, "byteLength": 36 }],
+            "buffers": [{ "byteLength": 36 }]
+        }"#;
+        let bin = f32_bytes(&[0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0]);
+        let model = Model::from_bytes(gltf.to_vec())
+            .unwrap()
+            .set_position(types::Vec3::new(1.0, 2.0, 3.0));
+        let model = Model {
+            bin_file: Cow::Owned(bin),
+            ..model
+        };
+
+        let data = std::thread::spawn(move || model.load())
+            .join()
+            .unwrap()
+            .unwrap();
+        assert_eq!(data.primitives.len(), 1);
+        assert_eq!(data.primitives[0].vertices.len(), 3 * mesh::VERTEX_SIZE);
+        assert_eq!(data.primitives[0].indices, vec![0, 1, 2]);
+        assert_eq!(data.position, nalgebra_glm::vec3(1.0, 2.0, 3.0));
+    }
+
+    #[test]
+    fn watches_and_reloads_models_read_from_files() {
+        let directory = std::env::temp_dir().join(format!("trs_24-reload-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("scene.gltf");
+        std::fs::write(
+            &path,
+            r#"{ "buffers": [{ "uri": "scene%20data.bin", "byteLength": 1 }] }"#,
+        )
+        .unwrap();
+        std::fs::write(directory.join("scene data.bin"), [0]).unwrap();
+
+        let model = Model::from_path(&path)
+            .unwrap()
+            .set_normal_mode(types::NormalMode::Flat);
+        let data = model.load().unwrap();
+        assert_eq!(
+            data.watched_files,
+            vec![path.clone(), directory.join("scene data.bin")]
+        );
+
+        let source = data.source.unwrap();
+        assert!(source.gltf_file.is_empty());
+        std::fs::write(&path, r#"{ "asset": { "version": "2.0" } }"#).unwrap();
+        let reloaded = source.reload().unwrap();
+        assert_eq!(reloaded.normal_mode, types::NormalMode::Flat);
+        assert_eq!(reloaded.load().unwrap().watched_files, vec![path]);
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
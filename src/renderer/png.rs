@@ -0,0 +1,139 @@
+/// Encodes 8-bit RGBA pixels, rows from the top down, as a PNG file. The image
+/// data is stored without compression, which every decoder reads and needs no
+/// compression library, at the cost of files slightly larger than the pixels.
+pub fn encode(width: u32, height: u32, rgba: &[u8]) -> Vec<u8> {
+    let row = width as usize * 4;
+    let mut scanlines = Vec::with_capacity((row + 1) * height as usize);
+    for pixels in rgba.chunks_exact(row).take(height as usize) {
+        // Filter type 0, the row is stored as it is
+        scanlines.push(0);
+        scanlines.extend_from_slice(pixels);
+    }
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&width.to_be_bytes());
+    header.extend_from_slice(&height.to_be_bytes());
+    // 8 bits per channel, RGBA, deflate, no filtering extensions, not interlaced
+    header.extend_from_slice(&[8, 6, 0, 0, 0]);
+
+    let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+    write_chunk(&mut png, b"IHDR", &header);
+    write_chunk(&mut png, b"IDAT", &zlib(&scanlines));
+    write_chunk(&mut png, b"IEND", &[]);
+
+    png
+}
+
+fn write_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+    let crc = crc32(&png[start..]);
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+/// The CRC-32 lookup table, one entry per byte value.
+const CRC_TABLE: [u32; 256] = crc_table();
+
+const fn crc_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut n = 0;
+    while n < 256 {
+        let mut c = n as u32;
+        let mut k = 0;
+        while k < 8 {
+            c = if c & 1 != 0 {
+                0xEDB8_8320 ^ (c >> 1)
+            } else {
+                c >> 1
+            };
+            k += 1;
+        }
+        table[n] = c;
+        n += 1;
+    }
+
+    table
+}
+
+fn crc32(bytes: &[u8]) -> u32 {
+    !bytes.iter().fold(!0u32, |crc, byte| {
+        CRC_TABLE[((crc ^ *byte as u32) & 0xFF) as usize] ^ (crc >> 8)
+    })
+}
+
+fn adler32(bytes: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    // 5552 is the most bytes that can be summed before `b` could overflow
+    for chunk in bytes.chunks(5552) {
+        for byte in chunk {
+            a += *byte as u32;
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+
+    (b << 16) | a
+}
+
+/// The most bytes a stored deflate block can hold.
+const MAX_STORED: usize = 65535;
+
+/// Wraps `data` in a zlib stream of stored, uncompressed deflate blocks.
+fn zlib(data: &[u8]) -> Vec<u8> {
+    let blocks = data.len().div_ceil(MAX_STORED).max(1);
+    let mut stream = Vec::with_capacity(2 + blocks * 5 + data.len() + 4);
+    // 32K window, no preset dictionary, fastest compression level
+    stream.extend_from_slice(&[0x78, 0x01]);
+
+    let mut chunks = data.chunks(MAX_STORED).peekable();
+    if chunks.peek().is_none() {
+        // An empty stream still needs a final block
+        stream.extend_from_slice(&[1, 0, 0, 0xFF, 0xFF]);
+    }
+    while let Some(chunk) = chunks.next() {
+        // Stored blocks start on a byte, the header's other bits are padding
+        let last = chunks.peek().is_none();
+        stream.push(last as u8);
+        let length = chunk.len() as u16;
+        stream.extend_from_slice(&length.to_le_bytes());
+        stream.extend_from_slice(&(!length).to_le_bytes());
+        stream.extend_from_slice(chunk);
+    }
+    stream.extend_from_slice(&adler32(data).to_be_bytes());
+
+    stream
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::texture::Image;
+
+    #[test]
+    fn encodes_pngs_that_decode_to_the_same_pixels() {
+        // Over 64 KiB of scanlines, so they take several stored blocks
+        let (width, height) = (137, 123);
+        let rgba: Vec<u8> = (0..width * height * 4)
+            .map(|i| match i % 4 {
+                3 => 255,
+                channel => ((i / 64) as u8).wrapping_mul(31) ^ (channel as u8 * (i % 7) as u8),
+            })
+            .collect();
+
+        let png = encode(width, height, &rgba);
+        let image = Image::decode(&png).unwrap();
+
+        assert_eq!((image.width, image.height), (width as i32, height as i32));
+        assert_eq!(image.pixels, rgba);
+    }
+
+    #[test]
+    fn checksums_match_the_reference_values() {
+        assert_eq!(crc32(b"IEND"), 0xAE42_6082);
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        assert_eq!(adler32(b"Wikipedia"), 0x11E6_0398);
+    }
+}
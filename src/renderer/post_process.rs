@@ -0,0 +1,476 @@
+use crate::renderer::assets;
+use crate::renderer::gl;
+use crate::renderer::gl_object::GlObject;
+use crate::renderer::material::{Material, ReadyMaterial, Uniform};
+use crate::renderer::render_target::RenderTarget;
+use crate::renderer::shader;
+use crate::renderer::texture;
+
+use std::borrow::Cow;
+use std::error::Error;
+use std::fmt;
+use std::rc::Rc;
+
+/// A full-screen pass run over the finished frame. Built-in effects keep their
+/// parameters in uniforms of the same name, which can be changed while they run
+/// through [`PostProcess::material_mut`].
+#[derive(Clone, Debug)]
+pub enum Effect {
+    /// Blends the frame towards its luma. The `amount` uniform starts at 1.0.
+    Grayscale,
+    /// Darkens the frame towards the corners. Distances go from 0.0 in the center
+    /// to 1.0 in the corners, shading starts `softness` before `radius`.
+    Vignette { radius: f32, softness: f32 },
+    /// Adds a glow around whatever is brighter than `threshold`, sampled up to
+    /// `radius` pixels away.
+    Bloom {
+        threshold: f32,
+        intensity: f32,
+        radius: f32,
+    },
+    /// Maps colors through a 256x16 lookup image of 16 slices of 16x16 side by side.
+    /// Blue picks the slice from left to right, red grows to the right and green
+    /// downwards within a slice, as in `assets/luts/identity.png`.
+    ColorGrading(Cow<'static, [u8]>),
+    /// Smooths jagged edges.
+    Fxaa,
+    /// A material made with [`Material::post_process`].
+    Custom(Material),
+}
+
+impl Effect {
+    fn material(&self) -> Material {
+        match self {
+            Effect::Grayscale => {
+                Material::post_process(include_bytes!("./shaders/grayscale-frag.glsl"))
+                    .set_uniform("amount", Uniform::Float(1.0))
+            }
+            Effect::Vignette { radius, softness } => {
+                Material::post_process(include_bytes!("./shaders/vignette-frag.glsl"))
+                    .set_uniform("radius", Uniform::Float(*radius))
+                    .set_uniform("softness", Uniform::Float(*softness))
+            }
+            Effect::Bloom {
+                threshold,
+                intensity,
+                radius,
+            } => Material::post_process(include_bytes!("./shaders/bloom-frag.glsl"))
+                .set_uniform("threshold", Uniform::Float(*threshold))
+                .set_uniform("intensity", Uniform::Float(*intensity))
+                .set_uniform("radius", Uniform::Float(*radius)),
+            // The lookup texture needs its own sampler, it's uploaded separately
+            Effect::ColorGrading(_) => {
+                Material::post_process(include_bytes!("./shaders/color-grading-frag.glsl"))
+            }
+            Effect::Fxaa => Material::post_process(include_bytes!("./shaders/fxaa-frag.glsl")),
+            Effect::Custom(material) => material.clone(),
+        }
+    }
+}
+
+/// Reasons an [`Effect`] can't be added.
+#[derive(Debug)]
+pub enum PostProcessError {
+    Shader(shader::ShaderError),
+    /// A texture uniform, or the lookup image of [`Effect::ColorGrading`], isn't an image.
+    BadTexture {
+        uniform: String,
+        reason: String,
+    },
+}
+
+impl fmt::Display for PostProcessError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PostProcessError::Shader(err) => write!(f, "{}", err),
+            PostProcessError::BadTexture { uniform, reason } => {
+                write!(
+                    f,
+                    "texture for uniform {} can't be decoded: {}",
+                    uniform, reason
+                )
+            }
+        }
+    }
+}
+
+impl Error for PostProcessError {}
+
+struct Pass {
+    material: ReadyMaterial,
+    enabled: bool,
+}
+
+/// The chain of [`Effect`]s run after [`crate::renderer::Renderer::draw`], in the
+/// order they were added. While any is enabled, the frame is drawn into an offscreen
+/// color buffer and each pass reads the output of the one before.
+pub struct PostProcess {
+    gl: gl::Gl,
+    quad: GlObject,
+    passes: Vec<Pass>,
+    /// Drawn into and read from in turns, made once an effect is enabled.
+    targets: Vec<RenderTarget>,
+    /// The framebuffer and viewport the last pass draws into, while a frame is
+    /// drawn offscreen.
+    output: Option<(gl::types::GLuint, [gl::types::GLint; 4])>,
+    /// The viewport size the offscreen buffers couldn't be made for, and the
+    /// status GL gave them, see [`PostProcess::error`].
+    failure: Option<((u32, u32), gl::types::GLenum)>,
+}
+
+impl PostProcess {
+    pub fn new(gl: gl::Gl) -> PostProcess {
+        #[rustfmt::skip]
+        let corners: [f32; 8] = [
+            -1.0, -1.0,
+             1.0, -1.0,
+            -1.0,  1.0,
+             1.0,  1.0,
+        ];
+        let quad = GlObject::buffer(gl.clone());
+        unsafe {
+            gl.BindBuffer(gl::ARRAY_BUFFER, quad.id());
+            gl.BufferData(
+                gl::ARRAY_BUFFER,
+                std::mem::size_of_val(&corners) as gl::types::GLsizeiptr,
+                corners.as_ptr() as *const _,
+                gl::STATIC_DRAW,
+            );
+            gl.BindBuffer(gl::ARRAY_BUFFER, 0);
+        }
+
+        PostProcess {
+            gl,
+            quad,
+            passes: Vec::new(),
+            targets: Vec::new(),
+            output: None,
+            failure: None,
+        }
+    }
+
+    /// Builds the effect's program and uploads its textures, enabled, at the end of
+    /// the chain. Returns its index. The context has to be current.
+    pub fn push(
+        &mut self,
+        effect: Effect,
+        assets: &mut assets::AssetServer,
+    ) -> Result<usize, PostProcessError> {
+        let material = effect.material();
+        let images = material
+            .decode_textures()
+            .map_err(|(uniform, reason)| PostProcessError::BadTexture { uniform, reason })?;
+        let mut ready = ReadyMaterial::new(self.gl.clone(), &material, &images, assets)
+            .map_err(PostProcessError::Shader)?;
+
+        if let Effect::ColorGrading(lut) = &effect {
+            let image =
+                texture::Image::decode(lut).map_err(|reason| PostProcessError::BadTexture {
+                    uniform: "lut".to_string(),
+                    reason,
+                })?;
+            let sampler = texture::Sampler {
+                mag_filter: gl::LINEAR,
+                min_filter: gl::LINEAR,
+                wrap_s: gl::CLAMP_TO_EDGE,
+                wrap_t: gl::CLAMP_TO_EDGE,
+            };
+            let lut = texture::Texture::from_image(self.gl.clone(), &image, sampler);
+            ready.set_texture("lut", Rc::new(lut));
+        }
+
+        self.passes.push(Pass {
+            material: ready,
+            enabled: true,
+        });
+
+        Ok(self.passes.len() - 1)
+    }
+
+    /// Removes the effect at `index`, later ones move down by one.
+    pub fn remove(&mut self, index: usize) {
+        if index < self.passes.len() {
+            self.passes.remove(index);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.passes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.passes.is_empty()
+    }
+
+    pub fn is_enabled(&self, index: usize) -> bool {
+        self.passes.get(index).is_some_and(|pass| pass.enabled)
+    }
+
+    /// Turns the effect at `index` on or off from the next frame on.
+    pub fn set_enabled(&mut self, index: usize, enabled: bool) {
+        if let Some(pass) = self.passes.get_mut(index) {
+            pass.enabled = enabled;
+        }
+    }
+
+    /// The status of the incomplete framebuffer that turned the effects off, until
+    /// the viewport changes size and it's tried again.
+    pub fn error(&self) -> Option<gl::types::GLenum> {
+        self.failure.map(|(_, status)| status)
+    }
+
+    /// Lets the uniforms of the effect at `index` change while it runs.
+    pub fn material_mut(&mut self, index: usize) -> Option<&mut ReadyMaterial> {
+        self.passes.get_mut(index).map(|pass| &mut pass.material)
+    }
+
+    /// Redirects drawing into the offscreen color buffer if any effect is enabled,
+    /// sized like the viewport. Called by [`crate::renderer::Renderer::draw`] ahead
+    /// of clearing. Nothing is redirected while the viewport is empty.
+    pub fn begin(&mut self) {
+        if !self.passes.iter().any(|pass| pass.enabled) {
+            return;
+        }
+
+        let mut framebuffer = 0;
+        let mut viewport = [0; 4];
+        unsafe {
+            self.gl
+                .GetIntegerv(gl::FRAMEBUFFER_BINDING, &mut framebuffer);
+            self.gl.GetIntegerv(gl::VIEWPORT, viewport.as_mut_ptr());
+        }
+        let (width, height) = (viewport[2] as u32, viewport[3] as u32);
+        if width == 0 || height == 0 {
+            return;
+        }
+        if self
+            .failure
+            .is_some_and(|(size, _)| size == (width, height))
+        {
+            return;
+        }
+
+        let sized = self.targets.iter().try_for_each(|target| {
+            if (target.width(), target.height()) == (width, height) {
+                Ok(())
+            } else {
+                target.resize(width, height)
+            }
+        });
+        let ready = sized.and_then(|_| {
+            while self.targets.len() < 2 {
+                self.targets
+                    .push(RenderTarget::new(self.gl.clone(), width, height)?);
+            }
+            Ok(())
+        });
+        // Effects are skipped rather than leaving the screen blank
+        if let Err(status) = ready {
+            self.targets.clear();
+            self.failure = Some(((width, height), status));
+            return;
+        }
+        self.failure = None;
+
+        self.output = Some((framebuffer as gl::types::GLuint, viewport));
+        self.targets[0].bind();
+    }
+
+    /// Runs the enabled effects over what was drawn since [`PostProcess::begin`],
+    /// the last one into the framebuffer that was bound before.
+    pub fn finish(&mut self) {
+        let Some((framebuffer, viewport)) = self.output.take() else {
+            return;
+        };
+
+        let passes: Vec<&Pass> = self.passes.iter().filter(|pass| pass.enabled).collect();
+        let [x, y, width, height] = viewport;
+        let texel_size = nalgebra_glm::vec2(1.0 / width as f32, 1.0 / height as f32);
+
+        unsafe {
+            self.gl.Disable(gl::BLEND);
+            self.gl.Disable(gl::DEPTH_TEST);
+            self.gl.BindBuffer(gl::ARRAY_BUFFER, self.quad.id());
+        }
+
+        let mut source = 0;
+        for (index, pass) in passes.iter().enumerate() {
+            if index + 1 == passes.len() {
+                unsafe {
+                    self.gl.BindFramebuffer(gl::FRAMEBUFFER, framebuffer);
+                    self.gl.Viewport(x, y, width, height);
+                }
+            } else {
+                self.targets[1 - source].bind();
+            }
+
+            pass.material.bind();
+            let program = pass.material.program();
+            let locations = program.locations();
+            self.targets[source].texture().bind();
+            program.set_int(locations.frame, 0);
+            program.set_vec2(locations.texel_size, &texel_size);
+            program.set_attribute(locations.position, 2, 2, 0);
+
+            unsafe { self.gl.DrawArrays(gl::TRIANGLE_STRIP, 0, 4) };
+            source = 1 - source;
+        }
+
+        unsafe {
+            self.gl.BindTexture(gl::TEXTURE_2D, 0);
+            self.gl.BindBuffer(gl::ARRAY_BUFFER, 0);
+            self.gl.Enable(gl::BLEND);
+        }
+    }
+}
+
+#[cfg(all(test, egl_backend))]
+mod tests {
+    use super::*;
+    use crate::renderer::{Headless, HeadlessError};
+    use crate::types;
+
+    fn headless() -> Option<Headless> {
+        match Headless::new(16, 16, &vec![], &vec![]) {
+            Ok(headless) => Some(headless),
+            Err(HeadlessError::Unavailable(reason)) => {
+                eprintln!("Skipping, {}", reason);
+                None
+            }
+            Err(err) => panic!("{}", err),
+        }
+    }
+
+    #[test]
+    fn toggles_effects_at_runtime() {
+        let Some(mut headless) = headless() else {
+            return;
+        };
+        let red = types::RGBA::new(1.0, 0.0, 0.0, 1.0);
+        let grayscale = headless.renderer().add_effect(Effect::Grayscale).unwrap();
+
+        let image = headless.render(1, &red);
+        assert!(image.pixels[..3]
+            .iter()
+            .all(|channel| channel.abs_diff(76) <= 1));
+
+        headless
+            .renderer()
+            .post_process
+            .set_enabled(grayscale, false);
+        let image = headless.render(1, &red);
+        assert_eq!(&image.pixels[..4], [255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn chains_effects_that_keep_flat_colors() {
+        let Some(mut headless) = headless() else {
+            return;
+        };
+        let lut = include_bytes!("../../assets/luts/identity.png");
+        for effect in [
+            Effect::ColorGrading(Cow::Borrowed(lut)),
+            Effect::Fxaa,
+            Effect::Vignette {
+                radius: 2.0,
+                softness: 0.5,
+            },
+        ] {
+            headless.renderer().add_effect(effect).unwrap();
+        }
+
+        let image = headless.render(1, &types::RGBA::new(0.2, 0.6, 0.4, 1.0));
+        for pixel in image.pixels.chunks_exact(4) {
+            for (channel, expected) in pixel.iter().zip([51, 153, 102, 255]) {
+                assert!(channel.abs_diff(expected) <= 2, "{:?}", pixel);
+            }
+        }
+
+        let err = headless
+            .renderer()
+            .add_effect(Effect::ColorGrading(Cow::Borrowed(b"not an image")))
+            .unwrap_err();
+        assert!(matches!(err, PostProcessError::BadTexture { .. }));
+    }
+
+    #[test]
+    fn blooms_around_bright_areas() {
+        let square = crate::ui::Element::new(types::Element::Shape(crate::ui::ShapeBuilder::new(
+            types::Shape::Square,
+        )))
+        .set_color(types::RGBA::new(1.0, 1.0, 1.0, 1.0))
+        .set_scale(types::Vec3::new(0.25, 0.25, 0.25))
+        .is_hud(true);
+        let mut headless = match Headless::new(32, 32, &vec![], &vec![square]) {
+            Ok(headless) => headless,
+            Err(HeadlessError::Unavailable(reason)) => {
+                eprintln!("Skipping, {}", reason);
+                return;
+            }
+            Err(err) => panic!("{}", err),
+        };
+        let black = types::RGBA::new(0.0, 0.0, 0.0, 1.0);
+        let red = |pixels: &[u8], x: usize| pixels[(16 * 32 + x) * 4];
+
+        // The first dark pixel left of the square's edge, on the middle row
+        let plain = headless.render(1, &black).pixels;
+        let edge = (0..16).find(|x| red(&plain, *x) == 255).unwrap();
+        assert_eq!(red(&plain, edge - 1), 0);
+
+        headless
+            .renderer()
+            .add_effect(Effect::Bloom {
+                threshold: 0.5,
+                intensity: 1.0,
+                radius: 4.0,
+            })
+            .unwrap();
+        let bloomed = headless.render(1, &black).pixels;
+        assert!(red(&bloomed, edge - 1) > 0);
+        assert_eq!(red(&bloomed, 16), 255);
+        // Too far for the glow to reach
+        assert_eq!(&bloomed[..4], [0, 0, 0, 255]);
+        assert_eq!(headless.renderer().post_process.error(), None);
+    }
+
+    #[test]
+    fn runs_custom_materials() {
+        let Some(mut headless) = headless() else {
+            return;
+        };
+        let invert = Material::post_process(
+            b"precision mediump float;
+            varying vec2 uv;
+            uniform sampler2D frame;
+            uniform vec3 tint;
+            void main() {
+                gl_FragColor = vec4(tint - texture2D(frame, uv).rgb, 1.0);
+            }",
+        )
+        .set_uniform("tint", Uniform::Vec3(nalgebra_glm::vec3(1.0, 1.0, 0.0)));
+        headless
+            .renderer()
+            .add_effect(Effect::Custom(invert))
+            .unwrap();
+
+        let image = headless.render(1, &types::RGBA::new(0.2, 0.0, 0.0, 1.0));
+        for (channel, expected) in image.pixels[..4].iter().zip([204, 255, 0, 255]) {
+            assert!(channel.abs_diff(expected) <= 1, "{:?}", &image.pixels[..4]);
+        }
+    }
+
+    #[test]
+    fn skips_empty_viewports() {
+        let Some(mut headless) = headless() else {
+            return;
+        };
+        let renderer = headless.renderer();
+        renderer.add_effect(Effect::Grayscale).unwrap();
+        unsafe { renderer.gl().Viewport(0, 0, 0, 0) };
+
+        renderer.post_process.begin();
+        renderer.post_process.finish();
+        assert!(renderer.post_process.targets.is_empty());
+        assert_eq!(renderer.post_process.error(), None);
+    }
+}
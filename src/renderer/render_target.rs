@@ -0,0 +1,177 @@
+use crate::renderer::gl;
+use crate::renderer::gl_object::GlObject;
+use crate::renderer::texture;
+
+use std::rc::Rc;
+
+/// A framebuffer with a color texture and a depth buffer, to render a view into
+/// with [`crate::renderer::Renderer::draw_to`] and show it on models and UI shapes,
+/// e.g. for minimaps, security camera monitors or mirrors.
+pub struct RenderTarget {
+    gl: gl::Gl,
+    framebuffer: GlObject,
+    color: Rc<texture::Texture>,
+    depth: GlObject,
+}
+
+impl RenderTarget {
+    /// Fails with the status GL gives the framebuffer if it isn't complete.
+    /// The context has to be current.
+    pub fn new(gl: gl::Gl, width: u32, height: u32) -> Result<RenderTarget, gl::types::GLenum> {
+        let target = RenderTarget {
+            framebuffer: GlObject::framebuffer(gl.clone()),
+            color: Rc::new(texture::Texture::empty(
+                gl.clone(),
+                width as i32,
+                height as i32,
+            )),
+            depth: GlObject::renderbuffer(gl.clone()),
+            gl,
+        };
+        // A renderbuffer only exists once it's been bound, which this does
+        target.allocate_depth(width, height);
+
+        target.with_framebuffer(|gl| unsafe {
+            gl.FramebufferTexture2D(
+                gl::FRAMEBUFFER,
+                gl::COLOR_ATTACHMENT0,
+                gl::TEXTURE_2D,
+                target.color.id(),
+                0,
+            );
+            gl.FramebufferRenderbuffer(
+                gl::FRAMEBUFFER,
+                gl::DEPTH_ATTACHMENT,
+                gl::RENDERBUFFER,
+                target.depth.id(),
+            );
+        });
+        target.status()?;
+
+        Ok(target)
+    }
+
+    /// Gives the attachments a new size, their contents are lost. Textures handed
+    /// out by [`RenderTarget::texture`] keep showing the target.
+    pub fn resize(&self, width: u32, height: u32) -> Result<(), gl::types::GLenum> {
+        self.color.resize(width as i32, height as i32);
+        self.allocate_depth(width, height);
+
+        self.status()
+    }
+
+    fn allocate_depth(&self, width: u32, height: u32) {
+        unsafe {
+            self.gl.BindRenderbuffer(gl::RENDERBUFFER, self.depth.id());
+            self.gl.RenderbufferStorage(
+                gl::RENDERBUFFER,
+                gl::DEPTH_COMPONENT16,
+                width as gl::types::GLsizei,
+                height as gl::types::GLsizei,
+            );
+            self.gl.BindRenderbuffer(gl::RENDERBUFFER, 0);
+        }
+    }
+
+    fn status(&self) -> Result<(), gl::types::GLenum> {
+        let status =
+            self.with_framebuffer(|gl| unsafe { gl.CheckFramebufferStatus(gl::FRAMEBUFFER) });
+        if status != gl::FRAMEBUFFER_COMPLETE {
+            return Err(status);
+        }
+
+        Ok(())
+    }
+
+    /// Runs `f` with the framebuffer bound, then binds the previous one again.
+    fn with_framebuffer<T>(&self, f: impl FnOnce(&gl::Gl) -> T) -> T {
+        let mut previous = 0;
+        unsafe {
+            self.gl.GetIntegerv(gl::FRAMEBUFFER_BINDING, &mut previous);
+            self.gl
+                .BindFramebuffer(gl::FRAMEBUFFER, self.framebuffer.id());
+        }
+        let result = f(&self.gl);
+        unsafe {
+            self.gl
+                .BindFramebuffer(gl::FRAMEBUFFER, previous as gl::types::GLuint);
+        }
+
+        result
+    }
+
+    /// Renders into the target from now on, over all of it.
+    pub fn bind(&self) {
+        unsafe {
+            self.gl
+                .BindFramebuffer(gl::FRAMEBUFFER, self.framebuffer.id());
+            self.gl
+                .Viewport(0, 0, self.color.width(), self.color.height());
+        }
+    }
+
+    /// The color attachment, for `ReadyModel::set_texture` or
+    /// [`crate::ui::ShapeBuilder::set_texture`]. Its rows run bottom-up.
+    pub fn texture(&self) -> Rc<texture::Texture> {
+        self.color.clone()
+    }
+
+    pub fn width(&self) -> u32 {
+        self.color.width() as u32
+    }
+
+    pub fn height(&self) -> u32 {
+        self.color.height() as u32
+    }
+}
+
+#[cfg(all(test, egl_backend))]
+mod tests {
+    use super::*;
+    use crate::renderer::{Camera, Headless, HeadlessError};
+    use crate::types;
+    use crate::ui;
+
+    #[test]
+    fn shows_what_was_drawn_into_it_on_a_shape() {
+        let square = ui::Element::new(types::Element::Shape(ui::ShapeBuilder::new(
+            types::Shape::Square,
+        )))
+        .set_color(types::RGBA::new(1.0, 1.0, 1.0, 1.0))
+        .set_scale(types::Vec3::new(0.5, 0.5, 0.5))
+        .is_hud(true);
+
+        let mut headless = match Headless::new(32, 32, &vec![], &vec![square]) {
+            Ok(headless) => headless,
+            Err(HeadlessError::Unavailable(reason)) => {
+                eprintln!("Skipping, {}", reason);
+                return;
+            }
+            Err(err) => panic!("{}", err),
+        };
+        let renderer = headless.renderer();
+        let target = RenderTarget::new(renderer.gl().clone(), 8, 8).unwrap();
+        renderer
+            .render_targets
+            .push((target, Camera::new(8.0, 8.0)));
+        let texture = renderer.render_targets[0].0.texture();
+        let types::Element::Shape(shape) = &mut renderer.ui[0] else {
+            unreachable!();
+        };
+        shape.set_texture(texture);
+
+        let image = headless.render(1, &types::RGBA::new(0.0, 0.0, 1.0, 1.0));
+        assert_eq!((image.width, image.height), (32, 32));
+        let pixel = |x: usize, y: usize| &image.pixels[(y * 32 + x) * 4..][..4];
+        assert_eq!(pixel(0, 0), [0, 0, 255, 255]);
+        assert_eq!(pixel(16, 16), [0, 0, 255, 255]);
+
+        // The shape keeps sampling the same texture at the new size
+        let renderer = headless.renderer();
+        renderer.render_targets[0].0.resize(16, 4).unwrap();
+        assert_eq!(renderer.render_targets[0].0.width(), 16);
+        assert_eq!(renderer.render_targets[0].0.texture().height(), 4);
+        let image = headless.render(1, &types::RGBA::new(0.0, 1.0, 0.0, 1.0));
+        assert_eq!(&image.pixels[(16 * 32 + 16) * 4..][..4], [0, 255, 0, 255]);
+    }
+}
@@ -1,7 +1,15 @@
+use crate::renderer::assets;
 use crate::renderer::camera::ProjectionType;
 use crate::renderer::gl;
+use crate::renderer::gl_object;
+use crate::renderer::loader;
 use crate::renderer::model;
+use crate::renderer::post_process;
+use crate::renderer::render_target;
 use crate::renderer::shader;
+use crate::renderer::texture;
+#[cfg(debug_assertions)]
+use crate::renderer::watcher;
 use crate::renderer::Camera;
 use crate::types;
 use crate::ui;
@@ -9,12 +17,69 @@ use crate::ui;
 use glutin::display::Display;
 use glutin::prelude::*;
 
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::rc::Rc;
+
+/// Sources of the built-in shaders, recompiled when they change in debug builds.
+#[cfg(debug_assertions)]
+const SHADER_3D_FILES: [&str; 2] = [
+    concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/src/renderer/shaders/shader-vert.glsl"
+    ),
+    concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/src/renderer/shaders/shader-frag.glsl"
+    ),
+];
+#[cfg(debug_assertions)]
+const SHADER_2D_FILES: [&str; 2] = [
+    concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/src/ui/shaders/shader-vert.glsl"
+    ),
+    concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/src/ui/shaders/shader-frag.glsl"
+    ),
+];
+
+/// Results of the work the renderer hands to its loader threads.
+enum Loaded {
+    Model(Result<model::ModelData, model::ModelError>),
+    Glyphs((u64, u32), Result<ui::GlyphBitmaps, ui::FontError>),
+    /// A new version of the model with this id, after its files changed.
+    Reload(u64, Result<model::ModelData, model::ModelError>),
+}
+
 pub struct Renderer {
-    program_3d: gl::types::GLuint,
-    program_2d: gl::types::GLuint,
+    program_3d: shader::ShaderProgram,
+    program_2d: shader::ShaderProgram,
     gl: gl::Gl,
+    context: gl_object::ContextId,
     pub models: Vec<model::ReadyModel>,
     pub ui: Vec<types::Element>,
+    /// Drawn at the start of every [`Renderer::draw`], each as its camera sees the models
+    pub render_targets: Vec<(render_target::RenderTarget, Camera)>,
+    /// Textures, mesh buffers and fonts shared between models and UI elements
+    pub assets: assets::AssetServer,
+    /// Full-screen effects run over every frame, see [`Renderer::add_effect`]
+    pub post_process: post_process::PostProcess,
+    /// Models that failed to load, by their index in the list passed to [`Renderer::new`]
+    pub model_errors: Vec<(usize, model::ModelError)>,
+    /// UI elements whose font failed to load, by their index in the list passed to [`Renderer::new`]
+    pub ui_errors: Vec<(usize, ui::FontError)>,
+    loader: loader::Loader<Loaded>,
+    /// Models back from the loader threads, by their index in the list passed to [`Renderer::new`]
+    loaded_models: Vec<Option<Result<model::ModelData, model::ModelError>>>,
+    /// Index of the next model to upload, later ones wait so the order is kept
+    next_model: usize,
+    /// UI elements not created yet, text waits for the glyphs of its font and size
+    pending_ui: VecDeque<(ui::Element, Option<(u64, u32)>)>,
+    loaded_fonts: HashMap<(u64, u32), Result<Rc<ui::Glyphs>, ui::FontError>>,
+    ui_count: usize,
+    #[cfg(debug_assertions)]
+    watcher: watcher::FileWatcher,
 }
 
 impl Renderer {
@@ -22,7 +87,7 @@ impl Renderer {
         gl_display: &Display,
         not_ready_models: &Vec<model::Model>,
         not_ready_ui: &Vec<ui::Element>,
-    ) -> Self {
+    ) -> Result<Self, shader::ShaderError> {
         unsafe {
             let gl = gl::Gl::load_with(|symbol| {
                 let symbol = std::ffi::CString::new(symbol).unwrap();
@@ -37,6 +102,11 @@ impl Renderer {
                 (!s.is_null()).then(|| std::ffi::CStr::from_ptr(s.cast()))
             }
 
+            // The context is current on this thread from here on, GL objects
+            // dropped on it are deleted right away
+            let context = gl_object::ContextId::new();
+            gl_object::set_current_context(Some(context));
+
             if let Some(renderer) = get_gl_string(&gl, gl::RENDERER) {
                 println!("Running on {}", renderer.to_string_lossy());
             }
@@ -48,137 +118,310 @@ impl Renderer {
                 println!("Shaders version on {}", shaders_version.to_string_lossy());
             }
 
-            let program_3d = shader::create_init_shader(
+            let program_3d = shader::ShaderProgram::new(
                 gl.clone(),
                 include_bytes!("./shaders/shader-vert.glsl"),
                 include_bytes!("./shaders/shader-frag.glsl"),
-            );
-            let program_2d = shader::create_init_shader(
+            )?;
+            let program_2d = shader::ShaderProgram::new(
                 gl.clone(),
                 include_bytes!("../ui/shaders/shader-vert.glsl"),
                 include_bytes!("../ui/shaders/shader-frag.glsl"),
-            );
+            )?;
 
             gl.Enable(gl::BLEND);
             gl.BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
 
-            let mut models = Vec::new();
-
+            // Parsing, decoding and rasterizing happen on loader threads, the results
+            // are uploaded by `poll_loading` as they come in
+            let mut loader = loader::Loader::new();
             for model in not_ready_models {
-                let mut x = model::ReadyModel::new(
-                    gl.clone(),
-                    model.gltf_file,
-                    model.bin_file,
-                    model.texture_file,
-                );
-                x.set_position(nalgebra_glm::vec3(
-                    model.position.x,
-                    model.position.y,
-                    model.position.z,
-                ));
-                x.set_scale(nalgebra_glm::vec3(
-                    model.scale.x,
-                    model.scale.y,
-                    model.scale.z,
-                ));
-                match model.rotation.1 {
-                    types::RotAxis::Pitch => {
-                        x.set_rotation(nalgebra_glm::quat_angle_axis(
-                            model.rotation.0,
-                            &nalgebra_glm::vec3(1.0, 0.0, 0.0),
-                        ));
-                    }
-                    types::RotAxis::Roll => {
-                        x.set_rotation(nalgebra_glm::quat_angle_axis(
-                            model.rotation.0,
-                            &nalgebra_glm::vec3(0.0, 1.0, 0.0),
-                        ));
-                    }
-                    types::RotAxis::Yaw => {
-                        x.set_rotation(nalgebra_glm::quat_angle_axis(
-                            model.rotation.0,
-                            &nalgebra_glm::vec3(0.0, 0.0, 1.0),
-                        ));
-                    }
-                }
-
-                models.push(x);
+                let model = model.clone();
+                loader.submit(move || Loaded::Model(model.load()));
             }
 
-            let mut ui: Vec<types::Element> = Vec::new();
-
+            let mut fonts = HashSet::new();
+            let mut pending_ui = VecDeque::new();
             for element in not_ready_ui {
-                match &element.el_type {
-                    types::Element::Shape(shape_builder) => {
-                        let x = types::Element::Shape(ui::ShapeBuilder::new_instance(
-                            gl.clone(),
-                            shape_builder,
-                            element.is_hud,
-                            &element.color,
-                            nalgebra_glm::vec3(
-                                element.position.x,
-                                element.position.y,
-                                element.position.z,
-                            ),
-                            nalgebra_glm::vec3(element.scale.x, element.scale.y, element.scale.z),
-                            nalgebra_glm::quat_angle_axis(
-                                element.rotation,
-                                &nalgebra_glm::vec3(0.0, 0.0, 1.0),
-                            ),
-                        ));
-                        ui.push(x);
-                    }
+                let font = match &element.el_type {
                     types::Element::Text(text_builder) => {
-                        let x = types::Element::Text(ui::TextBuilder::new_instance(
-                            gl.clone(),
-                            text_builder,
-                            element.is_hud,
-                            element.color.clone(),
-                            nalgebra_glm::vec3(
-                                element.position.x,
-                                element.position.y,
-                                element.position.z,
-                            ),
-                            nalgebra_glm::vec3(element.scale.x, element.scale.y, element.scale.z),
-                            nalgebra_glm::quat_angle_axis(
-                                element.rotation,
-                                &nalgebra_glm::vec3(0.0, 0.0, 1.0),
-                            ),
-                        ));
-                        ui.push(x);
+                        let key = (
+                            assets::content_key(&[text_builder.font()]),
+                            text_builder.size(),
+                        );
+                        if fonts.insert(key) {
+                            let font = text_builder.font().to_vec();
+                            let size = text_builder.size();
+                            loader.submit(move || {
+                                Loaded::Glyphs(key, ui::GlyphBitmaps::rasterize(&font, size))
+                            });
+                        }
+                        Some(key)
                     }
-                }
+                    types::Element::Shape(_) => None,
+                };
+                pending_ui.push_back((element.clone(), font));
             }
 
-            Self {
+            Ok(Self {
                 program_3d,
                 program_2d,
+                assets: assets::AssetServer::new(gl.clone()),
+                post_process: post_process::PostProcess::new(gl.clone()),
                 gl,
-                models,
-                ui,
+                context,
+                models: Vec::new(),
+                ui: Vec::new(),
+                render_targets: Vec::new(),
+                model_errors: Vec::new(),
+                ui_errors: Vec::new(),
+                loader,
+                loaded_models: (0..not_ready_models.len()).map(|_| None).collect(),
+                next_model: 0,
+                pending_ui,
+                loaded_fonts: HashMap::new(),
+                ui_count: not_ready_ui.len(),
+                #[cfg(debug_assertions)]
+                watcher: watcher::FileWatcher::new(std::time::Duration::from_millis(500)),
+            })
+        }
+    }
+
+    /// Uploads whatever the loader threads finished since the last call. Models and UI
+    /// elements are added in the order they were passed to [`Renderer::new`]. Called
+    /// at the start of every [`Renderer::draw`].
+    pub fn poll_loading(&mut self) {
+        while let Some((ticket, loaded)) = self.loader.poll() {
+            self.receive(ticket, loaded);
+        }
+        self.upload_loaded();
+    }
+
+    /// Blocks until every model and UI element passed to [`Renderer::new`] is ready.
+    pub fn finish_loading(&mut self) {
+        while let Some((ticket, loaded)) = self.loader.wait() {
+            self.receive(ticket, loaded);
+        }
+        self.upload_loaded();
+    }
+
+    /// Share of the models and UI elements passed to [`Renderer::new`] that are ready,
+    /// from 0.0 to 1.0, for a loading screen. Models that failed to load count as ready.
+    pub fn loading_progress(&self) -> f32 {
+        let total = self.loaded_models.len() + self.ui_count;
+        if total == 0 {
+            return 1.0;
+        }
+
+        let ready = self.next_model + self.ui_count - self.pending_ui.len();
+        ready as f32 / total as f32
+    }
+
+    pub fn is_loading(&self) -> bool {
+        self.next_model < self.loaded_models.len() || !self.pending_ui.is_empty()
+    }
+
+    /// Recompiles the built-in shaders and reloads models from [`model::Model::from_path`]
+    /// once their files change on disk. Whatever fails to compile or load keeps its
+    /// previous version. Called at the start of every [`Renderer::draw`] in debug builds.
+    #[cfg(debug_assertions)]
+    pub fn hot_reload(&mut self) {
+        let shader_files = SHADER_3D_FILES
+            .iter()
+            .chain(SHADER_2D_FILES.iter())
+            .map(std::path::Path::new);
+        let model_files = self
+            .models
+            .iter()
+            .flat_map(|model| model.watched_files())
+            .map(|file| file.as_path());
+        let changed = self.watcher.poll(shader_files.chain(model_files));
+        if changed.is_empty() {
+            return;
+        }
+
+        let changed_any = |files: &[&str]| {
+            files.iter().any(|file| {
+                changed
+                    .iter()
+                    .any(|changed| changed.as_path() == std::path::Path::new(file))
+            })
+        };
+        if changed_any(&SHADER_3D_FILES) {
+            reload_program(&self.gl, &mut self.program_3d, SHADER_3D_FILES);
+        }
+        if changed_any(&SHADER_2D_FILES) {
+            reload_program(&self.gl, &mut self.program_2d, SHADER_2D_FILES);
+        }
+
+        for model in &self.models {
+            let Some(source) = model.source() else {
+                continue;
+            };
+            if !model
+                .watched_files()
+                .iter()
+                .any(|file| changed.contains(file))
+            {
+                continue;
+            }
+
+            let source = source.clone();
+            let id = model.id();
+            self.loader
+                .submit(move || Loaded::Reload(id, source.reload().and_then(|model| model.load())));
+        }
+    }
+
+    fn receive(&mut self, ticket: usize, loaded: Loaded) {
+        match loaded {
+            // Models are submitted first, their tickets are their indices
+            Loaded::Model(result) => self.loaded_models[ticket] = Some(result),
+            Loaded::Glyphs(key, result) => {
+                let glyphs = result.map(|bitmaps| self.assets.glyphs(&bitmaps));
+                self.loaded_fonts.insert(key, glyphs);
+            }
+            // A failed reload leaves the previous version in place
+            Loaded::Reload(id, result) => {
+                let Some(model) = self.models.iter_mut().find(|model| model.id() == id) else {
+                    return;
+                };
+                let result =
+                    result.and_then(|data| unsafe { model.replace(data, &mut self.assets) });
+                if let Err(err) = result {
+                    eprintln!("Failed to reload model: {}", err);
+                }
             }
         }
     }
 
+    fn upload_loaded(&mut self) {
+        while let Some(result) = self
+            .loaded_models
+            .get_mut(self.next_model)
+            .and_then(Option::take)
+        {
+            let index = self.next_model;
+            self.next_model += 1;
+
+            let result = result.and_then(|data| unsafe {
+                model::ReadyModel::upload(self.gl.clone(), data, &mut self.assets)
+            });
+            match result {
+                Ok(model) => self.models.push(model),
+                Err(err) => {
+                    eprintln!("Failed to load model {}: {}", index, err);
+                    self.model_errors.push((index, err));
+                }
+            }
+        }
+
+        while let Some((_, font)) = self.pending_ui.front() {
+            let glyphs = match font {
+                Some(key) => match self.loaded_fonts.get(key) {
+                    Some(glyphs) => Some(glyphs.clone()),
+                    None => break,
+                },
+                None => None,
+            };
+            let index = self.ui_count - self.pending_ui.len();
+            let (element, _) = self.pending_ui.pop_front().unwrap();
+            let result = glyphs
+                .transpose()
+                .and_then(|glyphs| self.instantiate(&element, glyphs));
+            match result {
+                Ok(instance) => self.ui.push(instance),
+                Err(err) => {
+                    eprintln!("Failed to load UI element {}: {}", index, err);
+                    self.ui_errors.push((index, err));
+                }
+            }
+        }
+
+        if self.pending_ui.is_empty() {
+            self.loaded_fonts.clear();
+        }
+    }
+
+    fn instantiate(
+        &mut self,
+        element: &ui::Element,
+        glyphs: Option<Rc<ui::Glyphs>>,
+    ) -> Result<types::Element, ui::FontError> {
+        let position =
+            nalgebra_glm::vec3(element.position.x, element.position.y, element.position.z);
+        let scale = nalgebra_glm::vec3(element.scale.x, element.scale.y, element.scale.z);
+        let rotation =
+            nalgebra_glm::quat_angle_axis(element.rotation, &nalgebra_glm::vec3(0.0, 0.0, 1.0));
+
+        Ok(match &element.el_type {
+            types::Element::Shape(shape_builder) => {
+                types::Element::Shape(ui::ShapeBuilder::new_instance(
+                    self.gl.clone(),
+                    shape_builder,
+                    element.is_hud,
+                    &element.color,
+                    position,
+                    scale,
+                    rotation,
+                ))
+            }
+            types::Element::Text(text_builder) => {
+                let glyphs = match glyphs {
+                    Some(glyphs) => glyphs,
+                    None => self.assets.font(text_builder.font(), text_builder.size())?,
+                };
+                types::Element::Text(ui::TextBuilder::new_instance(
+                    self.gl.clone(),
+                    text_builder,
+                    glyphs,
+                    element.is_hud,
+                    element.color.clone(),
+                    position,
+                    scale,
+                    rotation,
+                ))
+            }
+        })
+    }
+
+    /// Adds a full-screen effect at the end of the chain run after each frame, and
+    /// returns its index in [`Renderer::post_process`], where it can be turned off.
+    pub fn add_effect(
+        &mut self,
+        effect: post_process::Effect,
+    ) -> Result<usize, post_process::PostProcessError> {
+        self.post_process.push(effect, &mut self.assets)
+    }
+
+    /// Advances model animations by `dt` seconds.
+    pub fn update(&mut self, dt: f32) {
+        for model in self.models.iter_mut() {
+            model.update(dt);
+        }
+    }
+
     pub fn draw(&mut self, world_color: &types::RGBA, camera: &Camera) {
+        // Another renderer's context may have been current on this thread since
+        gl_object::set_current_context(Some(self.context));
+        gl_object::flush_deletions(&self.gl);
+        #[cfg(debug_assertions)]
+        self.hot_reload();
+        self.poll_loading();
+
+        for (target, target_camera) in &self.render_targets {
+            self.draw_to(target, world_color, target_camera);
+        }
+
+        self.post_process.begin();
         unsafe {
             self.gl
                 .ClearColor(world_color.r, world_color.g, world_color.b, world_color.a);
             self.gl.Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
+        }
+        self.draw_models(camera);
 
-            camera.adjust(
-                self.gl.clone(),
-                self.program_3d,
-                ProjectionType::Perspective,
-                45.0,
-                0.1,
-                100.0,
-            );
-            self.gl.Enable(gl::DEPTH_TEST);
-            for model in &self.models {
-                model.draw(self.program_3d);
-            }
-
+        unsafe {
             self.gl.Clear(gl::DEPTH_BUFFER_BIT);
 
             self.gl.Disable(gl::DEPTH_TEST);
@@ -186,8 +429,7 @@ impl Renderer {
                 match element {
                     types::Element::Shape(shape_instance) => {
                         camera.adjust(
-                            self.gl.clone(),
-                            self.program_2d,
+                            &self.program_2d,
                             if shape_instance.is_hud {
                                 ProjectionType::Orthographic
                             } else {
@@ -197,12 +439,11 @@ impl Renderer {
                             0.1,
                             100.0,
                         );
-                        shape_instance.draw(self.program_2d);
+                        shape_instance.draw(&self.program_2d);
                     }
                     types::Element::Text(text_instance) => {
                         camera.adjust(
-                            self.gl.clone(),
-                            self.program_2d,
+                            &self.program_2d,
                             if text_instance.is_hud {
                                 ProjectionType::Orthographic
                             } else {
@@ -213,16 +454,155 @@ impl Renderer {
                             100.0,
                         );
 
-                        text_instance.draw(self.program_2d);
+                        text_instance.draw(&self.program_2d);
+                    }
+                }
+            }
+        }
+
+        self.post_process.finish();
+    }
+
+    /// Draws the models into `target` as `camera` sees them, over a `world_color`
+    /// background. UI elements are left out, they belong on the screen. The
+    /// framebuffer and viewport bound before are bound again afterwards.
+    pub fn draw_to(
+        &self,
+        target: &render_target::RenderTarget,
+        world_color: &types::RGBA,
+        camera: &Camera,
+    ) {
+        let mut framebuffer = 0;
+        let mut viewport = [0; 4];
+        unsafe {
+            self.gl
+                .GetIntegerv(gl::FRAMEBUFFER_BINDING, &mut framebuffer);
+            self.gl.GetIntegerv(gl::VIEWPORT, viewport.as_mut_ptr());
+        }
+
+        target.bind();
+        unsafe {
+            self.gl
+                .ClearColor(world_color.r, world_color.g, world_color.b, world_color.a);
+            self.gl.Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
+        }
+        self.draw_models(camera);
+
+        unsafe {
+            self.gl.Disable(gl::DEPTH_TEST);
+            self.gl
+                .BindFramebuffer(gl::FRAMEBUFFER, framebuffer as gl::types::GLuint);
+            let [x, y, width, height] = viewport;
+            self.gl.Viewport(x, y, width, height);
+        }
+    }
+
+    fn draw_models(&self, camera: &Camera) {
+        unsafe {
+            camera.adjust(
+                &self.program_3d,
+                ProjectionType::Perspective,
+                45.0,
+                0.1,
+                100.0,
+            );
+            self.gl.Enable(gl::DEPTH_TEST);
+            for model in &self.models {
+                match model.material() {
+                    Some(material) => {
+                        camera.adjust(
+                            material.program(),
+                            ProjectionType::Perspective,
+                            45.0,
+                            0.1,
+                            100.0,
+                        );
+                        material.bind();
+                        model.draw(material.program());
                     }
+                    None => model.draw(&self.program_3d),
                 }
             }
+
+            // Materials toggle face culling per mesh, UI elements expect it off
+            self.gl.Disable(gl::CULL_FACE);
         }
     }
 
+    /// Reads back what the last [`Renderer::draw`] left in the bound framebuffer, with
+    /// the top row first. Call it before swapping buffers, the back buffer is undefined
+    /// after a swap.
+    pub fn capture_frame(&self) -> texture::Image {
+        let mut viewport = [0; 4];
+        unsafe { self.gl.GetIntegerv(gl::VIEWPORT, viewport.as_mut_ptr()) };
+        let [x, y, width, height] = viewport;
+
+        let row = width as usize * 4;
+        let mut pixels = vec![0u8; row * height as usize];
+        unsafe {
+            self.gl.PixelStorei(gl::PACK_ALIGNMENT, 1);
+            self.gl.ReadPixels(
+                x,
+                y,
+                width,
+                height,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                pixels.as_mut_ptr().cast(),
+            );
+        }
+
+        // GL's origin is the bottom left corner
+        let pixels: Vec<u8> = pixels.chunks_exact(row).rev().flatten().copied().collect();
+        texture::Image {
+            key: assets::content_key(&[&pixels]),
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    pub fn gl(&self) -> &gl::Gl {
+        &self.gl
+    }
+
+    /// The context the renderer's GL objects belong to.
+    pub fn context(&self) -> gl_object::ContextId {
+        self.context
+    }
+
     pub fn resize(&self, width: i32, height: i32) {
         unsafe {
             self.gl.Viewport(0, 0, width, height);
         }
     }
 }
+
+/// Swaps `program` for one built from the current contents of `files`, if they compile.
+#[cfg(debug_assertions)]
+fn reload_program(gl: &gl::Gl, program: &mut shader::ShaderProgram, files: [&str; 2]) {
+    let sources = files
+        .iter()
+        .map(std::fs::read)
+        .collect::<std::io::Result<Vec<Vec<u8>>>>();
+    let result = match sources {
+        Ok(sources) => shader::ShaderProgram::new(gl.clone(), &sources[0], &sources[1])
+            .map_err(|err| err.to_string()),
+        Err(err) => Err(err.to_string()),
+    };
+
+    match result {
+        Ok(reloaded) => {
+            *program = reloaded;
+            println!("Reloaded {} and {}", files[0], files[1]);
+        }
+        Err(err) => eprintln!("Failed to reload {} and {}: {}", files[0], files[1], err),
+    }
+}
+
+impl Drop for Renderer {
+    fn drop(&mut self) {
+        gl_object::set_current_context(Some(self.context));
+        gl_object::flush_deletions(&self.gl);
+    }
+}
@@ -0,0 +1,155 @@
+use base64::Engine;
+
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+
+/// Fetches the files a glTF model references by URI, such as external buffers and images.
+/// `data:` URIs never reach a resolver, they are decoded by [`resolve_uri`].
+pub trait Resolver: Send + Sync {
+    fn resolve(&self, uri: &str) -> Result<Vec<u8>, String>;
+}
+
+/// Resolves URIs as paths relative to a directory, usually the one holding the `.gltf` file.
+pub struct DirectoryResolver {
+    directory: PathBuf,
+}
+
+impl DirectoryResolver {
+    pub fn new(directory: impl Into<PathBuf>) -> DirectoryResolver {
+        DirectoryResolver {
+            directory: directory.into(),
+        }
+    }
+}
+
+impl Resolver for DirectoryResolver {
+    fn resolve(&self, uri: &str) -> Result<Vec<u8>, String> {
+        let path = uri_to_path(&self.directory, uri)?;
+        std::fs::read(&path).map_err(|err| format!("{}: {}", path.display(), err))
+    }
+}
+
+/// Resolves URIs from files registered up front, e.g. with `include_bytes!`.
+#[derive(Default)]
+pub struct MemoryResolver {
+    files: HashMap<String, Vec<u8>>,
+}
+
+impl MemoryResolver {
+    pub fn new() -> MemoryResolver {
+        MemoryResolver::default()
+    }
+
+    /// Registers `bytes` under `uri`, exactly as the glTF spells it.
+    pub fn add_file(mut self, uri: &str, bytes: impl Into<Vec<u8>>) -> Self {
+        self.files.insert(uri.to_string(), bytes.into());
+        self
+    }
+}
+
+impl Resolver for MemoryResolver {
+    fn resolve(&self, uri: &str) -> Result<Vec<u8>, String> {
+        self.files
+            .get(uri)
+            .cloned()
+            .ok_or_else(|| "no file was registered under this URI".to_string())
+    }
+}
+
+/// Decodes embedded `data:` URIs and hands every other URI to `resolver`.
+pub fn resolve_uri(uri: &str, resolver: Option<&dyn Resolver>) -> Result<Vec<u8>, String> {
+    if uri.starts_with("data:") {
+        return decode_data_uri(uri).ok_or_else(|| "data URI isn't valid base64".to_string());
+    }
+
+    match resolver {
+        Some(resolver) => resolver.resolve(uri),
+        None => Err("external URIs need a resolver, see Model::set_resolver".to_string()),
+    }
+}
+
+/// The file a relative URI points to, seen from `directory`. URIs that could
+/// leave the directory, absolute ones or ones with `..` in them, are rejected.
+pub fn uri_to_path(directory: &Path, uri: &str) -> Result<PathBuf, String> {
+    let relative = PathBuf::from(percent_decode(uri)?);
+    let escapes = relative
+        .components()
+        .any(|component| !matches!(component, Component::Normal(_) | Component::CurDir));
+    if escapes {
+        return Err(format!("{} points outside of {}", uri, directory.display()));
+    }
+
+    Ok(directory.join(relative))
+}
+
+/// Decodes a base64 `data:` URI.
+fn decode_data_uri(uri: &str) -> Option<Vec<u8>> {
+    let (header, payload) = uri.strip_prefix("data:")?.split_once(',')?;
+    if !header.ends_with(";base64") {
+        return None;
+    }
+
+    base64::engine::general_purpose::STANDARD
+        .decode(payload)
+        .ok()
+}
+
+/// Undoes the `%XX` escapes glTF URIs use for spaces and other reserved characters.
+fn percent_decode(uri: &str) -> Result<String, String> {
+    let bytes = uri.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = uri
+                .get(i + 1..i + 3)
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                .ok_or_else(|| format!("{} has a malformed % escape", uri))?;
+            decoded.push(hex);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+
+    String::from_utf8(decoded).map_err(|_| format!("{} doesn't decode to UTF-8", uri))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_relative_uris_into_the_directory() {
+        let directory = Path::new("models/duck");
+        assert_eq!(
+            uri_to_path(directory, "textures/base%20color.png").unwrap(),
+            directory.join("textures").join("base color.png")
+        );
+        assert_eq!(
+            uri_to_path(directory, "./scene.bin").unwrap(),
+            directory.join("scene.bin")
+        );
+    }
+
+    #[test]
+    fn rejects_uris_that_leave_the_directory() {
+        let directory = Path::new("models/duck");
+        for uri in [
+            "../secret.bin",
+            "textures/../../secret.bin",
+            "%2E%2E/secret.bin",
+            "/etc/passwd",
+        ] {
+            assert!(uri_to_path(directory, uri).is_err(), "{}", uri);
+        }
+
+        let resolver = DirectoryResolver::new(directory);
+        assert!(resolver
+            .resolve("../secret.bin")
+            .unwrap_err()
+            .contains("outside"));
+    }
+}
@@ -1,13 +1,63 @@
 use crate::renderer::gl;
+use crate::renderer::gl_object::GlObject;
 
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+/// The step of building a shader program that failed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ShaderStage {
+    Vertex,
+    Fragment,
+    Link,
+}
+
+/// A shader that doesn't compile, or a program that doesn't link.
+#[derive(Debug)]
+pub struct ShaderError {
+    pub stage: ShaderStage,
+    /// The driver's info log.
+    pub log: String,
+    /// The first line the log complains about, when the driver names one.
+    pub line: Option<usize>,
+    /// The source code on that line.
+    pub source_line: Option<String>,
+}
+
+impl fmt::Display for ShaderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.stage {
+            ShaderStage::Vertex => write!(f, "vertex shader failed to compile")?,
+            ShaderStage::Fragment => write!(f, "fragment shader failed to compile")?,
+            ShaderStage::Link => write!(f, "shader program failed to link")?,
+        }
+        if let (Some(line), Some(source_line)) = (self.line, &self.source_line) {
+            write!(f, " at line {}: {}", line, source_line.trim())?;
+        }
+
+        write!(f, "\n{}", self.log.trim_end())
+    }
+}
+
+impl Error for ShaderError {}
+
+/// Compiles both stages and links them into a program.
 pub fn create_init_shader(
     gl: gl::Gl,
     vertex_shader_source: &[u8],
     fragment_shader_source: &[u8],
-) -> gl::types::GLuint {
+) -> Result<gl::types::GLuint, ShaderError> {
     unsafe {
-        let vertex_shader = create_shader(&gl, gl::VERTEX_SHADER, vertex_shader_source);
-        let fragment_shader = create_shader(&gl, gl::FRAGMENT_SHADER, fragment_shader_source);
+        let vertex_shader = create_shader(&gl, ShaderStage::Vertex, vertex_shader_source)?;
+        let fragment_shader =
+            match create_shader(&gl, ShaderStage::Fragment, fragment_shader_source) {
+                Ok(fragment_shader) => fragment_shader,
+                Err(err) => {
+                    gl.DeleteShader(vertex_shader);
+                    return Err(err);
+                }
+            };
 
         let program = gl.CreateProgram();
 
@@ -19,18 +69,393 @@ pub fn create_init_shader(
         gl.DeleteShader(vertex_shader);
         gl.DeleteShader(fragment_shader);
 
-        program
+        let mut status = 0;
+        gl.GetProgramiv(program, gl::LINK_STATUS, &mut status);
+        if status == 0 {
+            let log = program_log(&gl, program);
+            gl.DeleteProgram(program);
+            return Err(ShaderError {
+                stage: ShaderStage::Link,
+                log,
+                line: None,
+                source_line: None,
+            });
+        }
+
+        Ok(program)
     }
 }
 
+/// Where a program reads a uniform, see [`ShaderProgram::uniform`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct UniformLocation(gl::types::GLint);
+
+/// Where a program reads a vertex attribute, see [`ShaderProgram::attribute`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AttributeLocation(gl::types::GLuint);
+
+/// Locations of the uniforms and attributes the renderer sets itself, `None` for
+/// the ones a program doesn't use.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Locations {
+    pub matrix: Option<UniformLocation>,
+    pub cam_matrix: Option<UniformLocation>,
+    pub skinned: Option<UniformLocation>,
+    pub joint_matrices: Option<UniformLocation>,
+    pub tex0: Option<UniformLocation>,
+    pub flip_v: Option<UniformLocation>,
+    pub alpha_mask: Option<UniformLocation>,
+    pub base_color: Option<UniformLocation>,
+    pub alpha_cutoff: Option<UniformLocation>,
+    pub opaque: Option<UniformLocation>,
+    pub frame: Option<UniformLocation>,
+    pub texel_size: Option<UniformLocation>,
+    pub position: Option<AttributeLocation>,
+    pub normal: Option<AttributeLocation>,
+    pub color: Option<AttributeLocation>,
+    pub tex: Option<AttributeLocation>,
+    pub joints: Option<AttributeLocation>,
+    pub weights: Option<AttributeLocation>,
+}
+
+/// A linked program with the locations of its active uniforms and attributes looked
+/// up once, so drawing needs no lookups by name.
+pub struct ShaderProgram {
+    gl: gl::Gl,
+    program: GlObject,
+    uniforms: HashMap<String, gl::types::GLint>,
+    attributes: HashMap<String, gl::types::GLuint>,
+    locations: Locations,
+}
+
+impl ShaderProgram {
+    pub fn new(
+        gl: gl::Gl,
+        vertex_shader_source: &[u8],
+        fragment_shader_source: &[u8],
+    ) -> Result<ShaderProgram, ShaderError> {
+        let program = create_init_shader(gl.clone(), vertex_shader_source, fragment_shader_source)?;
+
+        unsafe {
+            let uniforms = active_names(&gl, program, false)
+                .into_iter()
+                .map(|name| {
+                    let location = gl.GetUniformLocation(program, name.as_ptr().cast());
+                    (variable_name(&name), location)
+                })
+                .collect();
+            let attributes = active_names(&gl, program, true)
+                .into_iter()
+                .map(|name| {
+                    let location = gl.GetAttribLocation(program, name.as_ptr().cast());
+                    (variable_name(&name), location as gl::types::GLuint)
+                })
+                .collect();
+
+            let mut program = ShaderProgram {
+                program: GlObject::program(gl.clone(), program),
+                gl,
+                uniforms,
+                attributes,
+                locations: Locations::default(),
+            };
+            program.locations = Locations {
+                matrix: program.uniform("matrix"),
+                cam_matrix: program.uniform("cam_matrix"),
+                skinned: program.uniform("skinned"),
+                joint_matrices: program.uniform("joint_matrices"),
+                tex0: program.uniform("tex0"),
+                flip_v: program.uniform("flip_v"),
+                alpha_mask: program.uniform("alpha_mask"),
+                base_color: program.uniform("base_color"),
+                alpha_cutoff: program.uniform("alpha_cutoff"),
+                opaque: program.uniform("opaque"),
+                frame: program.uniform("frame"),
+                texel_size: program.uniform("texel_size"),
+                position: program.attribute("position"),
+                normal: program.attribute("normal"),
+                color: program.attribute("color"),
+                tex: program.attribute("tex"),
+                joints: program.attribute("joints"),
+                weights: program.attribute("weights"),
+            };
+
+            Ok(program)
+        }
+    }
+
+    pub fn id(&self) -> gl::types::GLuint {
+        self.program.id()
+    }
+
+    /// Makes the program current. The setters below assume it is.
+    pub fn bind(&self) {
+        unsafe { self.gl.UseProgram(self.program.id()) };
+    }
+
+    /// Locations of the built-in uniforms and attributes, resolved when it was linked.
+    pub fn locations(&self) -> &Locations {
+        &self.locations
+    }
+
+    /// Location of an active uniform, `None` if the program doesn't use it. Meant to
+    /// be looked up once and kept, not every frame.
+    pub fn uniform(&self, name: &str) -> Option<UniformLocation> {
+        self.uniforms.get(name).copied().map(UniformLocation)
+    }
+
+    /// Location of an active attribute, `None` if the program doesn't use it.
+    pub fn attribute(&self, name: &str) -> Option<AttributeLocation> {
+        self.attributes.get(name).copied().map(AttributeLocation)
+    }
+
+    /// Points the attribute at `size` floats, `offset` floats into every vertex of
+    /// `stride` floats in the bound array buffer. The setters skip `None` locations.
+    pub fn set_attribute(
+        &self,
+        attribute: Option<AttributeLocation>,
+        size: usize,
+        stride: usize,
+        offset: usize,
+    ) {
+        let Some(AttributeLocation(location)) = attribute else {
+            return;
+        };
+
+        unsafe {
+            self.gl.VertexAttribPointer(
+                location,
+                size as gl::types::GLint,
+                gl::FLOAT,
+                gl::FALSE,
+                (stride * std::mem::size_of::<f32>()) as gl::types::GLsizei,
+                (offset * std::mem::size_of::<f32>()) as *const () as *const _,
+            );
+            self.gl.EnableVertexAttribArray(location);
+        }
+    }
+
+    pub fn set_int(&self, uniform: Option<UniformLocation>, value: i32) {
+        if let Some(UniformLocation(location)) = uniform {
+            unsafe { self.gl.Uniform1i(location, value) };
+        }
+    }
+
+    pub fn set_bool(&self, uniform: Option<UniformLocation>, value: bool) {
+        self.set_int(uniform, value as i32);
+    }
+
+    pub fn set_float(&self, uniform: Option<UniformLocation>, value: f32) {
+        if let Some(UniformLocation(location)) = uniform {
+            unsafe { self.gl.Uniform1f(location, value) };
+        }
+    }
+
+    pub fn set_vec2(&self, uniform: Option<UniformLocation>, value: &nalgebra_glm::Vec2) {
+        if let Some(UniformLocation(location)) = uniform {
+            unsafe { self.gl.Uniform2fv(location, 1, value.as_ptr()) };
+        }
+    }
+
+    pub fn set_vec3(&self, uniform: Option<UniformLocation>, value: &nalgebra_glm::Vec3) {
+        if let Some(UniformLocation(location)) = uniform {
+            unsafe { self.gl.Uniform3fv(location, 1, value.as_ptr()) };
+        }
+    }
+
+    pub fn set_vec4(&self, uniform: Option<UniformLocation>, value: &nalgebra_glm::Vec4) {
+        if let Some(UniformLocation(location)) = uniform {
+            unsafe { self.gl.Uniform4fv(location, 1, value.as_ptr()) };
+        }
+    }
+
+    pub fn set_mat4(&self, uniform: Option<UniformLocation>, value: &nalgebra_glm::Mat4) {
+        self.set_mat4_array(uniform, std::slice::from_ref(value));
+    }
+
+    /// Sets the first `values.len()` elements of a `mat4` array uniform.
+    pub fn set_mat4_array(&self, uniform: Option<UniformLocation>, values: &[nalgebra_glm::Mat4]) {
+        let Some(UniformLocation(location)) = uniform else {
+            return;
+        };
+
+        // nalgebra stores matrices as 16 column-major floats, back to back in a slice
+        unsafe {
+            self.gl.UniformMatrix4fv(
+                location,
+                values.len() as gl::types::GLsizei,
+                gl::FALSE,
+                values.as_ptr().cast(),
+            )
+        };
+    }
+}
+
+/// Names of the program's active uniforms, or with `attributes` its active
+/// attributes, NUL-terminated.
+unsafe fn active_names(gl: &gl::Gl, program: gl::types::GLuint, attributes: bool) -> Vec<Vec<u8>> {
+    let (count_param, max_length_param) = match attributes {
+        true => (gl::ACTIVE_ATTRIBUTES, gl::ACTIVE_ATTRIBUTE_MAX_LENGTH),
+        false => (gl::ACTIVE_UNIFORMS, gl::ACTIVE_UNIFORM_MAX_LENGTH),
+    };
+    let mut count = 0;
+    gl.GetProgramiv(program, count_param, &mut count);
+    let mut max_length = 0;
+    gl.GetProgramiv(program, max_length_param, &mut max_length);
+
+    (0..count as gl::types::GLuint)
+        .map(|index| {
+            let mut name = vec![0u8; max_length.max(1) as usize];
+            let (mut length, mut size, mut ty) = (0, 0, 0);
+            let get_active = match attributes {
+                true => gl::Gl::GetActiveAttrib,
+                false => gl::Gl::GetActiveUniform,
+            };
+            get_active(
+                gl,
+                program,
+                index,
+                max_length,
+                &mut length,
+                &mut size,
+                &mut ty,
+                name.as_mut_ptr().cast(),
+            );
+            name.truncate(length as usize);
+            name.push(0);
+            name
+        })
+        .collect()
+}
+
+/// The name a uniform is set by. Drivers report arrays as their first element,
+/// `joint_matrices[0]`, which is also where setting the whole array starts.
+fn variable_name(reported: &[u8]) -> String {
+    let name = String::from_utf8_lossy(reported);
+    let name = name.trim_end_matches('\0');
+
+    name.strip_suffix("[0]").unwrap_or(name).to_string()
+}
+
 unsafe fn create_shader(
     gl: &gl::Gl,
-    shader: gl::types::GLenum,
+    stage: ShaderStage,
     source: &[u8],
-) -> gl::types::GLuint {
-    let shader = gl.CreateShader(shader);
+) -> Result<gl::types::GLuint, ShaderError> {
+    let shader_type = match stage {
+        ShaderStage::Vertex => gl::VERTEX_SHADER,
+        _ => gl::FRAGMENT_SHADER,
+    };
+    let shader = gl.CreateShader(shader_type);
     let len = source.len() as gl::types::GLint;
     gl.ShaderSource(shader, 1, [source.as_ptr().cast()].as_ptr(), &len);
     gl.CompileShader(shader);
-    shader
+
+    let mut status = 0;
+    gl.GetShaderiv(shader, gl::COMPILE_STATUS, &mut status);
+    if status == 0 {
+        let log = shader_log(gl, shader);
+        gl.DeleteShader(shader);
+
+        let line = error_line(&log);
+        let source_line = line.and_then(|line| {
+            String::from_utf8_lossy(source)
+                .lines()
+                .nth(line.checked_sub(1)?)
+                .map(str::to_string)
+        });
+        return Err(ShaderError {
+            stage,
+            log,
+            line,
+            source_line,
+        });
+    }
+
+    Ok(shader)
+}
+
+unsafe fn shader_log(gl: &gl::Gl, shader: gl::types::GLuint) -> String {
+    let mut len = 0;
+    gl.GetShaderiv(shader, gl::INFO_LOG_LENGTH, &mut len);
+    let mut log = vec![0u8; len.max(1) as usize];
+    gl.GetShaderInfoLog(shader, len, std::ptr::null_mut(), log.as_mut_ptr().cast());
+
+    String::from_utf8_lossy(&log)
+        .trim_end_matches('\0')
+        .to_string()
+}
+
+unsafe fn program_log(gl: &gl::Gl, program: gl::types::GLuint) -> String {
+    let mut len = 0;
+    gl.GetProgramiv(program, gl::INFO_LOG_LENGTH, &mut len);
+    let mut log = vec![0u8; len.max(1) as usize];
+    gl.GetProgramInfoLog(program, len, std::ptr::null_mut(), log.as_mut_ptr().cast());
+
+    String::from_utf8_lossy(&log)
+        .trim_end_matches('\0')
+        .to_string()
+}
+
+/// Finds the line number in an info log. Drivers prefix messages with the source
+/// string and the line, as `0:12(5):` (Mesa), `0(12) :` (NVIDIA) or `ERROR: 0:12:` (ANGLE).
+fn error_line(log: &str) -> Option<usize> {
+    log.lines().find_map(|message| {
+        let message = message.trim_start();
+        let message = message
+            .strip_prefix("ERROR:")
+            .unwrap_or(message)
+            .trim_start();
+
+        let rest = message.trim_start_matches(|c: char| c.is_ascii_digit());
+        if rest.len() == message.len() {
+            return None;
+        }
+        let rest = rest.strip_prefix(':').or_else(|| rest.strip_prefix('('))?;
+
+        let digits = &rest[..rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len())];
+        digits.parse().ok()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_the_line_in_driver_logs() {
+        assert_eq!(error_line("0:12(5): error: `colr' undeclared"), Some(12));
+        assert_eq!(
+            error_line("0(7) : error C1008: undefined variable \"colr\""),
+            Some(7)
+        );
+        assert_eq!(
+            error_line("ERROR: 0:3: 'colr' : undeclared identifier\nERROR: 1 compilation errors."),
+            Some(3)
+        );
+        assert_eq!(error_line("error: linking failed"), None);
+    }
+
+    #[test]
+    fn names_arrays_by_their_first_element() {
+        assert_eq!(variable_name(b"joint_matrices[0]\0"), "joint_matrices");
+        assert_eq!(variable_name(b"lights[0].color\0"), "lights[0].color");
+        assert_eq!(variable_name(b"cam_matrix\0"), "cam_matrix");
+    }
+
+    #[test]
+    fn hands_matrix_arrays_to_gl_as_they_are_stored() {
+        let matrices = [
+            nalgebra_glm::Mat4::identity(),
+            nalgebra_glm::translation(&nalgebra_glm::vec3(1.0, 2.0, 3.0)),
+        ];
+        let floats = unsafe { std::slice::from_raw_parts(matrices.as_ptr().cast::<f32>(), 32) };
+
+        assert_eq!(&floats[..16], matrices[0].as_slice());
+        assert_eq!(&floats[16..], matrices[1].as_slice());
+        // Column-major, the translation is the last column
+        assert_eq!(&floats[28..31], [1.0, 2.0, 3.0]);
+    }
 }
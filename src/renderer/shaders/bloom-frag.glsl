@@ -0,0 +1,30 @@
+precision mediump float;
+
+varying vec2 uv;
+
+uniform sampler2D frame;
+uniform vec2 texel_size;
+uniform float threshold;
+uniform float intensity;
+uniform float radius;
+
+vec3 bright(vec2 offset) {
+    vec3 color = texture2D(frame, uv + offset * texel_size).rgb;
+    return max(color - threshold, 0.0);
+}
+
+void main() {
+    vec4 color = texture2D(frame, uv);
+
+    // Two rings of 8 taps around the center, nearer taps weigh double
+    vec3 glow = bright(vec2(0.0)) * 2.0;
+    for (int i = 0; i < 8; i++) {
+        float angle = float(i) * 0.78539816;
+        vec2 direction = vec2(cos(angle), sin(angle));
+        glow += bright(direction * radius * 0.5) * 2.0;
+        glow += bright(direction * radius);
+    }
+    glow /= 26.0;
+
+    gl_FragColor = vec4(color.rgb + glow * intensity, color.a);
+}
@@ -0,0 +1,21 @@
+precision mediump float;
+
+varying vec2 uv;
+
+uniform sampler2D frame;
+// 16 slices of 16x16 side by side, see `Effect::ColorGrading`
+uniform sampler2D lut;
+
+void main() {
+    vec4 color = texture2D(frame, uv);
+    vec3 graded = clamp(color.rgb, 0.0, 1.0) * 15.0;
+
+    // Texel centers, so neighbouring slices don't bleed in
+    float x = (graded.r + 0.5) / 256.0;
+    float y = (graded.g + 0.5) / 16.0;
+    float slice = floor(graded.b);
+    vec3 lower = texture2D(lut, vec2(slice / 16.0 + x, y)).rgb;
+    vec3 upper = texture2D(lut, vec2(min(slice + 1.0, 15.0) / 16.0 + x, y)).rgb;
+
+    gl_FragColor = vec4(mix(lower, upper, graded.b - slice), color.a);
+}
@@ -0,0 +1,45 @@
+precision mediump float;
+
+// The console variant of FXAA: blurs along edges found from the luma of the
+// four diagonal neighbours, falling back to a shorter blur where that overshoots
+#define REDUCE_MIN (1.0 / 128.0)
+#define REDUCE_MUL (1.0 / 8.0)
+#define SPAN_MAX 8.0
+
+varying vec2 uv;
+
+uniform sampler2D frame;
+uniform vec2 texel_size;
+
+void main() {
+    vec3 to_luma = vec3(0.299, 0.587, 0.114);
+    float luma_nw = dot(texture2D(frame, uv + vec2(-1.0, 1.0) * texel_size).rgb, to_luma);
+    float luma_ne = dot(texture2D(frame, uv + vec2(1.0, 1.0) * texel_size).rgb, to_luma);
+    float luma_sw = dot(texture2D(frame, uv + vec2(-1.0, -1.0) * texel_size).rgb, to_luma);
+    float luma_se = dot(texture2D(frame, uv + vec2(1.0, -1.0) * texel_size).rgb, to_luma);
+    vec4 color = texture2D(frame, uv);
+    float luma_m = dot(color.rgb, to_luma);
+
+    float luma_min = min(luma_m, min(min(luma_nw, luma_ne), min(luma_sw, luma_se)));
+    float luma_max = max(luma_m, max(max(luma_nw, luma_ne), max(luma_sw, luma_se)));
+
+    vec2 direction = vec2(
+        -((luma_nw + luma_ne) - (luma_sw + luma_se)),
+        (luma_nw + luma_sw) - (luma_ne + luma_se)
+    );
+    float reduce = max((luma_nw + luma_ne + luma_sw + luma_se) * 0.25 * REDUCE_MUL, REDUCE_MIN);
+    float scale = 1.0 / (min(abs(direction.x), abs(direction.y)) + reduce);
+    direction = clamp(direction * scale, vec2(-SPAN_MAX), vec2(SPAN_MAX)) * texel_size;
+
+    vec3 near = 0.5 * (
+        texture2D(frame, uv + direction * (1.0 / 3.0 - 0.5)).rgb +
+        texture2D(frame, uv + direction * (2.0 / 3.0 - 0.5)).rgb
+    );
+    vec3 far = near * 0.5 + 0.25 * (
+        texture2D(frame, uv - direction * 0.5).rgb +
+        texture2D(frame, uv + direction * 0.5).rgb
+    );
+    float luma_far = dot(far, to_luma);
+
+    gl_FragColor = vec4(luma_far < luma_min || luma_far > luma_max ? near : far, color.a);
+}
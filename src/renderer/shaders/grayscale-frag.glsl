@@ -0,0 +1,12 @@
+precision mediump float;
+
+varying vec2 uv;
+
+uniform sampler2D frame;
+uniform float amount;
+
+void main() {
+    vec4 color = texture2D(frame, uv);
+    float luma = dot(color.rgb, vec3(0.299, 0.587, 0.114));
+    gl_FragColor = vec4(mix(color.rgb, vec3(luma), amount), color.a);
+}
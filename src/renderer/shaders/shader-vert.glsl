@@ -1,20 +1,37 @@
 precision mediump float;
 
+// Must match mesh::MAX_JOINTS
+#define MAX_JOINTS 24
+
 attribute vec3 position;
 attribute vec3 normal;
-attribute vec3 color;
+attribute vec4 color;
 attribute vec2 tex;
+attribute vec4 joints;
+attribute vec4 weights;
 
-varying vec3 v_color;
+varying vec4 v_color;
 varying vec3 v_normal;
 varying vec2 tex_coord;
 
 uniform mat4 cam_matrix;
 uniform mat4 matrix;
+uniform bool skinned;
+uniform bool flip_v;
+uniform mat4 joint_matrices[MAX_JOINTS];
 
 void main() {
-    gl_Position = cam_matrix * matrix * vec4(position, 1.0);
+    mat4 skin = mat4(1.0);
+    if (skinned) {
+        skin = weights.x * joint_matrices[int(joints.x)]
+             + weights.y * joint_matrices[int(joints.y)]
+             + weights.z * joint_matrices[int(joints.z)]
+             + weights.w * joint_matrices[int(joints.w)];
+    }
+
+    gl_Position = cam_matrix * matrix * skin * vec4(position, 1.0);
     v_color = color;
-    v_normal = normal;
-    tex_coord = tex;
+    v_normal = (matrix * skin * vec4(normal, 0.0)).xyz;
+    // Textures rendered into start at the bottom row, images at the top
+    tex_coord = flip_v ? vec2(tex.x, 1.0 - tex.y) : tex;
 }
@@ -0,0 +1,15 @@
+precision mediump float;
+
+varying vec2 uv;
+
+uniform sampler2D frame;
+uniform float radius;
+uniform float softness;
+
+void main() {
+    vec4 color = texture2D(frame, uv);
+    // 0.0 in the center, 1.0 in the corners
+    float distance_to_center = distance(uv, vec2(0.5)) * 1.41421356;
+    float shade = 1.0 - smoothstep(radius - softness, radius, distance_to_center);
+    gl_FragColor = vec4(color.rgb * shade, color.a);
+}
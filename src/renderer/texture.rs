@@ -1,18 +1,45 @@
+use crate::renderer::assets;
 use crate::renderer::gl;
+use crate::renderer::gl_object::GlObject;
+use crate::renderer::png;
+use std::cell::Cell;
 use std::io::{Cursor, Read};
+use std::path::Path;
 
-#[allow(dead_code)]
-pub struct Texture {
-    gl: gl::Gl,
-    texture: gl::types::GLuint,
+/// Filtering and wrapping parameters applied when a texture is uploaded.
+/// Values are GL enums, which is also how glTF samplers store them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Sampler {
+    pub mag_filter: gl::types::GLenum,
+    pub min_filter: gl::types::GLenum,
+    pub wrap_s: gl::types::GLenum,
+    pub wrap_t: gl::types::GLenum,
 }
 
-#[allow(dead_code)]
-impl Texture {
-    pub fn new(
-        gl: gl::Gl,
-        img: &[u8],
-    ) -> Texture {
+impl Default for Sampler {
+    fn default() -> Self {
+        Self {
+            mag_filter: gl::NEAREST,
+            min_filter: gl::NEAREST_MIPMAP_LINEAR,
+            wrap_s: gl::REPEAT,
+            wrap_t: gl::REPEAT,
+        }
+    }
+}
+
+/// An image decoded to RGBA, ready to be uploaded. Decoding doesn't need a GL
+/// context, so it can happen on a loader thread.
+#[derive(Clone, Debug)]
+pub struct Image {
+    /// [`assets::content_key`] of the encoded bytes, for sharing textures.
+    pub key: u64,
+    pub width: i32,
+    pub height: i32,
+    pub pixels: Vec<u8>,
+}
+
+impl Image {
+    pub fn decode(img: &[u8]) -> Result<Image, String> {
         unsafe {
             let mut cursor = Cursor::new(img);
             let mut contents = vec![];
@@ -22,6 +49,7 @@ impl Texture {
             let mut image_height = 0;
             let mut num_color_channels = 0;
 
+            // Every image is expanded to RGBA regardless of its channel count
             let bytes = stb_image_rust::stbi_load_from_memory(
                 contents.as_mut_ptr(),
                 contents.len() as i32,
@@ -31,86 +59,227 @@ impl Texture {
                 stb_image_rust::STBI_rgb_alpha,
             );
 
-            let mut texture: gl::types::GLuint = std::mem::zeroed();
-            gl.GenTextures(1, &mut texture);
+            if bytes.is_null() {
+                return Err(format!(
+                    "Image data ({} bytes) is not a decodable PNG, JPEG, BMP, TGA or GIF",
+                    img.len()
+                ));
+            }
+
+            let len = (image_width * image_height * 4) as usize;
+            let pixels = std::slice::from_raw_parts(bytes, len).to_vec();
+            stb_image_rust::stbi_image_free(bytes);
+
+            Ok(Image {
+                key: assets::content_key(&[img]),
+                width: image_width,
+                height: image_height,
+                pixels,
+            })
+        }
+    }
+
+    pub fn encode_png(&self) -> Vec<u8> {
+        png::encode(self.width as u32, self.height as u32, &self.pixels)
+    }
+
+    pub fn save_png(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        std::fs::write(path, self.encode_png())
+    }
+}
+
+#[allow(dead_code)]
+#[derive(Debug)]
+pub struct Texture {
+    gl: gl::Gl,
+    texture: GlObject,
+    width: Cell<i32>,
+    height: Cell<i32>,
+    bottom_up: bool,
+}
+
+#[allow(dead_code)]
+impl Texture {
+    pub fn new(
+        gl: gl::Gl,
+        img: &[u8],
+    ) -> Result<Texture, String> {
+        Texture::with_sampler(gl, img, Sampler::default())
+    }
+
+    pub fn with_sampler(
+        gl: gl::Gl,
+        img: &[u8],
+        sampler: Sampler,
+    ) -> Result<Texture, String> {
+        Ok(Texture::from_image(gl, &Image::decode(img)?, sampler))
+    }
+
+    /// Uploads an image that was already decoded, possibly on another thread.
+    pub fn from_image(gl: gl::Gl, image: &Image, sampler: Sampler) -> Texture {
+        Texture::upload(
+            gl,
+            image.width,
+            image.height,
+            image.pixels.as_ptr() as *const std::ffi::c_void,
+            sampler,
+        )
+    }
+
+    /// A single white texel, used by surfaces that don't sample any image.
+    pub fn white(gl: gl::Gl) -> Texture {
+        let white: [u8; 4] = [255, 255, 255, 255];
+        Texture::upload(
+            gl,
+            1,
+            1,
+            white.as_ptr() as *const std::ffi::c_void,
+            Sampler::default(),
+        )
+    }
+
+    /// An RGBA texture without contents, for a framebuffer to render into. It has no
+    /// mipmaps and clamps at the edges, so any size works on GLES2.
+    pub fn empty(gl: gl::Gl, width: i32, height: i32) -> Texture {
+        let texture = GlObject::texture(gl.clone());
+        let empty = Self {
+            gl,
+            texture,
+            width: Cell::new(0),
+            height: Cell::new(0),
+            bottom_up: true,
+        };
+
+        unsafe {
+            empty.gl.BindTexture(gl::TEXTURE_2D, empty.texture.id());
+            for (parameter, value) in [
+                (gl::TEXTURE_MIN_FILTER, gl::LINEAR),
+                (gl::TEXTURE_MAG_FILTER, gl::LINEAR),
+                (gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE),
+                (gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE),
+            ] {
+                empty.gl.TexParameteri(gl::TEXTURE_2D, parameter, value as gl::types::GLint);
+            }
+            empty.gl.BindTexture(gl::TEXTURE_2D, 0);
+        }
+        empty.resize(width, height);
+
+        empty
+    }
+
+    /// Replaces the contents of an [`Texture::empty`] texture with undefined ones
+    /// of a new size. Whatever samples it keeps doing so.
+    pub fn resize(&self, width: i32, height: i32) {
+        unsafe {
+            self.gl.BindTexture(gl::TEXTURE_2D, self.texture.id());
+            self.gl.TexImage2D(
+                gl::TEXTURE_2D,
+                0,
+                gl::RGBA as i32,
+                width,
+                height,
+                0,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                std::ptr::null(),
+            );
+            self.gl.BindTexture(gl::TEXTURE_2D, 0);
+        }
+        self.width.set(width);
+        self.height.set(height);
+    }
+
+    fn upload(
+        gl: gl::Gl,
+        width: i32,
+        height: i32,
+        rgba: *const std::ffi::c_void,
+        sampler: Sampler,
+    ) -> Texture {
+        unsafe {
+            let texture = GlObject::texture(gl.clone());
 
             gl.ActiveTexture(gl::TEXTURE0);
-            gl.BindTexture(gl::TEXTURE_2D, texture);
+            gl.BindTexture(gl::TEXTURE_2D, texture.id());
 
             gl.TexParameteri(
                 gl::TEXTURE_2D,
                 gl::TEXTURE_MIN_FILTER,
-                gl::NEAREST_MIPMAP_LINEAR.try_into().unwrap(),
+                sampler.min_filter as gl::types::GLint,
             );
             gl.TexParameteri(
                 gl::TEXTURE_2D,
                 gl::TEXTURE_MAG_FILTER,
-                gl::NEAREST.try_into().unwrap(),
+                sampler.mag_filter as gl::types::GLint,
             );
             gl.TexParameteri(
                 gl::TEXTURE_2D,
                 gl::TEXTURE_WRAP_S,
-                gl::REPEAT.try_into().unwrap(),
+                sampler.wrap_s as gl::types::GLint,
             );
             gl.TexParameteri(
                 gl::TEXTURE_2D,
                 gl::TEXTURE_WRAP_T,
-                gl::REPEAT.try_into().unwrap(),
+                sampler.wrap_t as gl::types::GLint,
             );
 
-            if num_color_channels == 4 {
-                gl.TexImage2D(
-                    gl::TEXTURE_2D,
-                    0,
-                    gl::RGBA as i32,
-                    image_width as i32,
-                    image_height as i32,
-                    0,
-                    gl::RGBA,
-                    gl::UNSIGNED_BYTE,
-                    bytes as *const std::ffi::c_void,
-                );
-            } else if num_color_channels == 3 {
-                gl.TexImage2D(
-                    gl::TEXTURE_2D,
-                    0,
-                    gl::RGBA as i32,
-                    image_width as i32,
-                    image_height as i32,
-                    0,
-                    gl::RGBA,
-                    gl::UNSIGNED_BYTE,
-                    bytes as *const std::ffi::c_void,
-                );
-            } else if num_color_channels == 1 {
-                gl.TexImage2D(
-                    gl::TEXTURE_2D,
-                    0,
-                    gl::RGBA as i32,
-                    image_width as i32,
-                    image_height as i32,
-                    0,
-                    gl::RED_BITS,
-                    gl::UNSIGNED_BYTE,
-                    bytes as *const std::ffi::c_void,
-                );
-            }
+            gl.TexImage2D(
+                gl::TEXTURE_2D,
+                0,
+                gl::RGBA as i32,
+                width,
+                height,
+                0,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                rgba,
+            );
 
             gl.GenerateMipmap(gl::TEXTURE_2D);
-
-            stb_image_rust::stbi_image_free(bytes);
             gl.BindTexture(gl::TEXTURE_2D, 0);
 
             Self {
                 gl,
-                texture
+                texture,
+                width: Cell::new(width),
+                height: Cell::new(height),
+                bottom_up: false,
             }
         }
     }
+
+    /// GPU memory taken by the RGBA texels, mipmaps included.
+    pub fn size_in_bytes(&self) -> usize {
+        (self.width.get() * self.height.get() * 4) as usize * 4 / 3
+    }
+
+    pub fn id(&self) -> gl::types::GLuint {
+        self.texture.id()
+    }
+
+    pub fn width(&self) -> i32 {
+        self.width.get()
+    }
+
+    pub fn height(&self) -> i32 {
+        self.height.get()
+    }
+
+    /// Whether the first row is the bottom one, as in textures rendered into.
+    /// Decoded images start at the top.
+    pub fn is_bottom_up(&self) -> bool {
+        self.bottom_up
+    }
+
     pub fn bind(&self) {
+        self.bind_to(0)
+    }
+
+    /// Binds the texture to texture unit `unit`, for shaders sampling several textures.
+    pub fn bind_to(&self, unit: u32) {
         unsafe {
-            self.gl.ActiveTexture(gl::TEXTURE0);
-            self.gl.BindTexture(gl::TEXTURE_2D, self.texture)
+            self.gl.ActiveTexture(gl::TEXTURE0 + unit);
+            self.gl.BindTexture(gl::TEXTURE_2D, self.texture.id())
         }
     }
     pub fn unbind(&self) {
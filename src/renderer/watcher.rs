@@ -0,0 +1,85 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+/// Notices files changing on disk by polling their modification times, which is
+/// plenty for hot reloading a handful of assets.
+pub struct FileWatcher {
+    interval: Duration,
+    last_poll: Option<Instant>,
+    /// Modification times seen on the last poll, `None` for files that were missing.
+    modified: HashMap<PathBuf, Option<SystemTime>>,
+}
+
+impl FileWatcher {
+    pub fn new(interval: Duration) -> FileWatcher {
+        FileWatcher {
+            interval,
+            last_poll: None,
+            modified: HashMap::new(),
+        }
+    }
+
+    /// Returns which of `files` changed since the previous poll. Files that show up
+    /// for the first time are only remembered, files left out are forgotten. Polls
+    /// closer together than the interval return nothing.
+    pub fn poll<'a>(&mut self, files: impl IntoIterator<Item = &'a Path>) -> Vec<PathBuf> {
+        let now = Instant::now();
+        if self
+            .last_poll
+            .is_some_and(|last_poll| now - last_poll < self.interval)
+        {
+            return Vec::new();
+        }
+        self.last_poll = Some(now);
+
+        let mut modified = HashMap::new();
+        let mut changed = Vec::new();
+        for file in files {
+            if modified.contains_key(file) {
+                continue;
+            }
+
+            // Editors that save by replacing the file leave it missing for a moment
+            let time = std::fs::metadata(file)
+                .and_then(|metadata| metadata.modified())
+                .ok();
+            if let Some(previous) = self.modified.get(file) {
+                if time.is_some() && *previous != time {
+                    changed.push(file.to_path_buf());
+                }
+            }
+            modified.insert(file.to_path_buf(), time);
+        }
+        self.modified = modified;
+
+        changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_files_modified_since_the_last_poll() {
+        let file = std::env::temp_dir().join(format!("trs_24-watcher-{}.txt", std::process::id()));
+        std::fs::write(&file, "a").unwrap();
+        let mut watcher = FileWatcher::new(Duration::ZERO);
+
+        assert!(watcher.poll([file.as_path()]).is_empty());
+        assert!(watcher.poll([file.as_path()]).is_empty());
+
+        let later = SystemTime::now() + Duration::from_secs(10);
+        std::fs::File::options()
+            .write(true)
+            .open(&file)
+            .unwrap()
+            .set_modified(later)
+            .unwrap();
+        assert_eq!(watcher.poll([file.as_path()]), vec![file.clone()]);
+        assert!(watcher.poll([file.as_path()]).is_empty());
+
+        std::fs::remove_file(&file).unwrap();
+    }
+}
@@ -1,12 +1,19 @@
 use crate::ui::ShapeBuilder;
 use crate::ui::TextBuilder;
 
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum RotAxis {
     Roll,
     Pitch,
     Yaw,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NormalMode {
+    Smooth,
+    Flat,
+}
+
 #[derive(Clone, Debug)]
 pub enum Shape {
     Square,
@@ -19,6 +26,7 @@ pub enum Element {
     Text(TextBuilder),
 }
 
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Vec3 {
     pub x: f32,
     pub y: f32,
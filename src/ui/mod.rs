@@ -3,5 +3,5 @@ mod text;
 mod shape;
 
 pub use ui::Element;
-pub use text::TextBuilder;
+pub use text::{FontError, GlyphBitmaps, Glyphs, TextBuilder};
 pub use shape::ShapeBuilder;
\ No newline at end of file
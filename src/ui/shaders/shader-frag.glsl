@@ -4,8 +4,13 @@ varying vec4 v_color;
 varying vec2 tex_coord;
 
 uniform sampler2D tex0;
+// Glyph bitmaps only hold coverage, in the red channel
+uniform bool alpha_mask;
 
 void main() {
-    vec4 sampled = vec4(1.0, 1.0, 1.0, texture2D(tex0, tex_coord).r);
+    vec4 sampled = texture2D(tex0, tex_coord);
+    if (alpha_mask) {
+        sampled = vec4(1.0, 1.0, 1.0, sampled.r);
+    }
     gl_FragColor = v_color * sampled;
 }
\ No newline at end of file
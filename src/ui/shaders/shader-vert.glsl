@@ -10,9 +10,10 @@ varying vec2 tex_coord;
 
 uniform mat4 cam_matrix;
 uniform mat4 matrix;
+uniform bool flip_v;
 
 void main() {
     gl_Position = cam_matrix * matrix * vec4(position, 0.0, 1.0);
     v_color = color;
-    tex_coord = tex;
+    tex_coord = flip_v ? vec2(tex.x, 1.0 - tex.y) : tex;
 }
@@ -1,5 +1,7 @@
 use crate::renderer::gl;
+use crate::renderer::{GlObject, ShaderProgram, Texture};
 use crate::types;
+use std::rc::Rc;
 
 #[derive(Clone, Debug)]
 pub struct ShapeBuilder {
@@ -10,9 +12,16 @@ pub struct ShapeBuilder {
     position: nalgebra_glm::Vec3,
     scale: nalgebra_glm::Vec3,
     rotation: nalgebra_glm::Quat,
-    vbo: gl::types::GLuint,
-    ebo: gl::types::GLuint,
-    texture: gl::types::GLuint,
+    objects: Option<Rc<ShapeObjects>>,
+    texture: Option<Rc<Texture>>,
+}
+
+/// GL objects of a shape instance, shared by its clones.
+#[derive(Debug)]
+struct ShapeObjects {
+    vbo: GlObject,
+    ebo: GlObject,
+    texture: GlObject,
 }
 
 impl ShapeBuilder {
@@ -29,9 +38,8 @@ impl ShapeBuilder {
                     0.0,
                     &nalgebra_glm::vec3(0.0, 0.0, 0.0),
                 ),
-                vbo: std::mem::zeroed(),
-                ebo: std::mem::zeroed(),
-                texture: std::mem::zeroed()
+                objects: None,
+                texture: None,
             }
         }
     }
@@ -53,10 +61,10 @@ impl ShapeBuilder {
                 #[rustfmt::skip]
                 types::Shape::Square => {
                     vertices = [
-                        -0.5, -0.5,  rgba.r, rgba.g, rgba.b, rgba.a,   0.0, 0.0, 
-                        -0.5,  0.5,  rgba.r, rgba.g, rgba.b, rgba.a,   0.0, 1.0, 
-                         0.5,  0.5,  rgba.r, rgba.g, rgba.b, rgba.a,   1.0, 1.0, 
-                         0.5, -0.5,  rgba.r, rgba.g, rgba.b, rgba.a,   1.0, 0.0,
+                        -0.5, -0.5,  rgba.r, rgba.g, rgba.b, rgba.a,   0.0, 1.0, 
+                        -0.5,  0.5,  rgba.r, rgba.g, rgba.b, rgba.a,   0.0, 0.0, 
+                         0.5,  0.5,  rgba.r, rgba.g, rgba.b, rgba.a,   1.0, 0.0, 
+                         0.5, -0.5,  rgba.r, rgba.g, rgba.b, rgba.a,   1.0, 1.0,
                     ]
                     .to_vec();
         
@@ -69,9 +77,9 @@ impl ShapeBuilder {
                 #[rustfmt::skip]
                 types::Shape::Triangle => {
                     vertices = [
-                         0.0,  0.5,  rgba.r, rgba.g, rgba.b, rgba.a,   0.5, 1.0,
-                        -0.5, -0.5,  rgba.r, rgba.g, rgba.b, rgba.a,   0.0, 0.0, 
-                         0.5, -0.5,  rgba.r, rgba.g, rgba.b, rgba.a,   1.0, 0.0,
+                         0.0,  0.5,  rgba.r, rgba.g, rgba.b, rgba.a,   0.5, 0.0,
+                        -0.5, -0.5,  rgba.r, rgba.g, rgba.b, rgba.a,   0.0, 1.0, 
+                         0.5, -0.5,  rgba.r, rgba.g, rgba.b, rgba.a,   1.0, 1.0,
                     ]
                     .to_vec();
         
@@ -81,9 +89,8 @@ impl ShapeBuilder {
                 }
             }
             
-            let mut vbo = std::mem::zeroed();
-            gl.GenBuffers(1, &mut vbo);
-            gl.BindBuffer(gl::ARRAY_BUFFER, vbo);
+            let vbo = GlObject::buffer(gl.clone());
+            gl.BindBuffer(gl::ARRAY_BUFFER, vbo.id());
             gl.BufferData(
                 gl::ARRAY_BUFFER,
                 (vertices.len() * std::mem::size_of::<f32>()) as gl::types::GLsizeiptr,
@@ -91,9 +98,8 @@ impl ShapeBuilder {
                 gl::STATIC_DRAW,
             );
 
-            let mut ebo = std::mem::zeroed();
-            gl.GenBuffers(1, &mut ebo);
-            gl.BindBuffer(gl::ELEMENT_ARRAY_BUFFER, ebo);
+            let ebo = GlObject::buffer(gl.clone());
+            gl.BindBuffer(gl::ELEMENT_ARRAY_BUFFER, ebo.id());
             gl.BufferData(
                 gl::ELEMENT_ARRAY_BUFFER,
                 (indices.len() * std::mem::size_of::<u32>()) as gl::types::GLsizeiptr,
@@ -101,9 +107,8 @@ impl ShapeBuilder {
                 gl::STATIC_DRAW,
             );
 
-            let mut texture = std::mem::zeroed();
-            gl.GenTextures(1, &mut texture);
-            gl.BindTexture(gl::TEXTURE_2D, texture);
+            let texture = GlObject::texture(gl.clone());
+            gl.BindTexture(gl::TEXTURE_2D, texture.id());
             let data: [u8; 1] = [255];
             gl.TexImage2D(
                 gl::TEXTURE_2D,
@@ -132,22 +137,43 @@ impl ShapeBuilder {
                 position,
                 scale,
                 rotation,
-                vbo,
-                ebo,
-                texture,
+                objects: Some(Rc::new(ShapeObjects { vbo, ebo, texture })),
+                texture: shape_builder.texture.clone(),
             }
         }
     }
 
-    pub fn draw(&self, program: gl::types::GLuint) {
+    /// Fills the shape with `texture`, tinted by its color, e.g. the one of a
+    /// [`crate::renderer::RenderTarget`] for a minimap. Texture coordinates span
+    /// the shape's bounding square.
+    pub fn set_texture(&mut self, texture: Rc<Texture>) {
+        self.texture = Some(texture);
+    }
+
+    pub fn draw(&self, program: &ShaderProgram) {
+        let Some(objects) = &self.objects else {
+            return;
+        };
+
         unsafe {
-            self.gl.UseProgram(program);
+            program.bind();
+            let locations = program.locations();
 
-            self.gl.BindBuffer(gl::ARRAY_BUFFER, self.vbo);
-            self.gl.BindBuffer(gl::ELEMENT_ARRAY_BUFFER, self.ebo);
+            self.gl.BindBuffer(gl::ARRAY_BUFFER, objects.vbo.id());
+            self.gl.BindBuffer(gl::ELEMENT_ARRAY_BUFFER, objects.ebo.id());
 
             self.gl.ActiveTexture(gl::TEXTURE0);
-            self.gl.BindTexture(gl::TEXTURE_2D, self.texture);
+            match &self.texture {
+                Some(texture) => {
+                    texture.bind();
+                    program.set_bool(locations.flip_v, texture.is_bottom_up());
+                }
+                None => {
+                    self.gl.BindTexture(gl::TEXTURE_2D, objects.texture.id());
+                    program.set_bool(locations.flip_v, false);
+                }
+            }
+            program.set_bool(locations.alpha_mask, false);
 
             let translation_matrix =
                 nalgebra_glm::translate(&nalgebra_glm::Mat4::identity(), &self.position);
@@ -156,55 +182,11 @@ impl ShapeBuilder {
 
             let model_matrix = translation_matrix * rotation_matrix * scale_matrix;
 
-            self.gl.UniformMatrix4fv(
-                self.gl
-                    .GetUniformLocation(program, b"matrix\0".as_ptr() as *const _),
-                1,
-                gl::FALSE,
-                model_matrix.as_slice().as_ptr() as *const f32,
-            );
+            program.set_mat4(locations.matrix, &model_matrix);
 
-            let pos_attrib = self
-                .gl
-                .GetAttribLocation(program, b"position\0".as_ptr() as *const _);
-            self.gl.VertexAttribPointer(
-                pos_attrib as gl::types::GLuint,
-                2,
-                gl::FLOAT,
-                gl::FALSE,
-                8 * std::mem::size_of::<f32>() as gl::types::GLsizei,
-                std::ptr::null(),
-            );
-            self.gl
-                .EnableVertexAttribArray(pos_attrib as gl::types::GLuint);
-
-            let color_attrib = self
-                .gl
-                .GetAttribLocation(program, b"color\0".as_ptr() as *const _);
-            self.gl.VertexAttribPointer(
-                color_attrib as gl::types::GLuint,
-                4,
-                gl::FLOAT,
-                gl::FALSE,
-                8 * std::mem::size_of::<f32>() as gl::types::GLsizei,
-                (2 * std::mem::size_of::<f32>()) as *const () as *const _,
-            );
-            self.gl
-                .EnableVertexAttribArray(color_attrib as gl::types::GLuint);
-
-            let tex_attrib = self
-                .gl
-                .GetAttribLocation(program, b"tex\0".as_ptr() as *const _);
-            self.gl.VertexAttribPointer(
-                tex_attrib as gl::types::GLuint,
-                2,
-                gl::FLOAT,
-                gl::FALSE,
-                8 * std::mem::size_of::<f32>() as gl::types::GLsizei,
-                (6 * std::mem::size_of::<f32>()) as *const () as *const _,
-            );
-            self.gl
-                .EnableVertexAttribArray(tex_attrib as gl::types::GLuint);
+            program.set_attribute(locations.position, 2, 8, 0);
+            program.set_attribute(locations.color, 4, 8, 2);
+            program.set_attribute(locations.tex, 2, 8, 6);
 
             self.gl.DrawElements(
                 gl::TRIANGLES,
@@ -1,11 +1,17 @@
 use crate::renderer::gl;
+use crate::renderer::{content_key, GlObject, ShaderProgram};
 use crate::types;
+use std::borrow::Cow;
 use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::path::Path;
+use std::rc::Rc;
 
 #[derive(Clone, Debug)]
 pub struct TextBuilder {
     text: String,
-    font: &'static [u8],
+    font: Cow<'static, [u8]>,
     size: u32,
     pub is_hud: bool,
     gl: gl::Gl,
@@ -13,8 +19,8 @@ pub struct TextBuilder {
     scale: nalgebra_glm::Vec3,
     rotation: nalgebra_glm::Quat,
     rgba: types::RGBA,
-    characters: HashMap<char, Character>,
-    vbo: gl::types::GLuint,
+    glyphs: Option<Rc<Glyphs>>,
+    vbo: Option<Rc<GlObject>>,
 }
 
 #[derive(Clone, Debug)]
@@ -25,60 +31,102 @@ struct Character {
     advance: i32,
 }
 
-impl TextBuilder {
-    pub fn new(text: String, font_data: &'static [u8], size: u32) -> Self {
-        unsafe {
-            Self {
-                text,
-                font: font_data,
-                size,
-                is_hud: false,
-                gl: std::mem::zeroed(),
-                position: nalgebra_glm::vec3(0.0, 0.0, 0.0),
-                scale: nalgebra_glm::vec3(1.0, 1.0, 1.0),
-                rotation: nalgebra_glm::quat_angle_axis(0.0, &nalgebra_glm::vec3(0.0, 0.0, 0.0)),
-                rgba: types::RGBA::new(0.1, 0.1, 0.1, 1.0),
-                characters: HashMap::new(),
-                vbo: std::mem::zeroed(),
+/// The ASCII glyphs of a font rasterized at one pixel size, one texture each.
+#[derive(Debug)]
+pub struct Glyphs {
+    gl: gl::Gl,
+    characters: HashMap<char, Character>,
+    textures: Vec<GlObject>,
+}
+
+/// The ASCII glyphs of a font rendered to bitmaps but not uploaded yet. Rendering
+/// doesn't need a GL context, so fonts can be rasterized on a loader thread.
+#[derive(Clone, Debug)]
+pub struct GlyphBitmaps {
+    /// [`content_key`] of the font the bitmaps were rendered from.
+    pub font_key: u64,
+    pub size: u32,
+    bitmaps: Vec<(char, Character, Vec<u8>)>,
+}
+
+/// Reasons a font can't be rasterized.
+#[derive(Clone, Debug)]
+pub enum FontError {
+    /// FreeType can't open the font at that size, e.g. the file isn't a font.
+    Invalid(String),
+    /// The font has no usable glyph for this character.
+    Glyph(char, String),
+}
+
+impl fmt::Display for FontError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FontError::Invalid(reason) => write!(f, "font can't be opened: {}", reason),
+            FontError::Glyph(c, reason) => {
+                write!(f, "glyph {:?} can't be rendered: {}", c, reason)
             }
         }
     }
+}
 
-    pub fn new_instance(
-        gl: gl::Gl,
-        text_builder: &TextBuilder,
-        is_hud: bool, 
-        rgba: types::RGBA,
-        position: nalgebra_glm::Vec3,
-        scale: nalgebra_glm::Vec3,
-        rotation: nalgebra_glm::Quat,
-    ) -> Self {
+impl Error for FontError {}
+
+impl GlyphBitmaps {
+    pub fn rasterize(font: &[u8], size: u32) -> Result<Self, FontError> {
+        let mut bitmaps = Vec::with_capacity(128);
+
+        let invalid = |err: freetype::Error| FontError::Invalid(err.to_string());
+        let ft = freetype::Library::init().map_err(invalid)?;
+        let face = ft.new_memory_face(font.to_vec(), 0).map_err(invalid)?;
+        face.set_pixel_sizes(0, size).map_err(invalid)?;
+
+        for c in 0..128u8 {
+            face.load_char(c as usize, freetype::face::LoadFlag::RENDER)
+                .map_err(|err| FontError::Glyph(c as char, err.to_string()))?;
+
+            let character = Character {
+                texture: 0,
+                size: (face.glyph().bitmap().width(), face.glyph().bitmap().rows()),
+                bearing: (face.glyph().bitmap_left(), face.glyph().bitmap_top()),
+                advance: face.glyph().advance().x as i32,
+            };
+
+            bitmaps.push((c as char, character, face.glyph().bitmap().buffer().to_vec()));
+        }
+
+        Ok(Self {
+            font_key: content_key(&[font]),
+            size,
+            bitmaps,
+        })
+    }
+}
+
+impl Glyphs {
+    pub fn rasterize(gl: gl::Gl, font: &[u8], size: u32) -> Result<Self, FontError> {
+        Ok(Glyphs::upload(gl, &GlyphBitmaps::rasterize(font, size)?))
+    }
+
+    pub fn upload(gl: gl::Gl, bitmaps: &GlyphBitmaps) -> Self {
         unsafe {
             let mut characters: HashMap<char, Character> = HashMap::new();
-
-            let ft = freetype::Library::init().unwrap();
-            let face = ft.new_memory_face(text_builder.font.to_vec(), 0).unwrap();
-            face.set_pixel_sizes(0, text_builder.size).unwrap();
+            let mut textures = Vec::with_capacity(bitmaps.bitmaps.len());
 
             gl.PixelStorei(gl::UNPACK_ALIGNMENT, 1);
             
-            for c in 0..128u8 {
-                face.load_char(c as usize, freetype::face::LoadFlag::RENDER)
-                    .unwrap();
-
-                let mut texture = 0;
-                gl.GenTextures(1, &mut texture);
-                gl.BindTexture(gl::TEXTURE_2D, texture);
+            for (c, character, bitmap) in &bitmaps.bitmaps {
+                let texture = GlObject::texture(gl.clone());
+                gl.BindTexture(gl::TEXTURE_2D, texture.id());
                 gl.TexImage2D(
                     gl::TEXTURE_2D,
                     0,
                     gl::LUMINANCE as gl::types::GLint,
-                    face.glyph().bitmap().width(),
-                    face.glyph().bitmap().rows(),
+                    character.size.0,
+                    character.size.1,
                     0,
                     gl::LUMINANCE,
                     gl::UNSIGNED_BYTE,
-                    face.glyph().bitmap().buffer().as_ptr() as *const std::ffi::c_void,
+                    bitmap.as_ptr() as *const std::ffi::c_void,
                 );
 
                 gl.TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as i32);
@@ -87,18 +135,78 @@ impl TextBuilder {
                 gl.TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
 
                 let character = Character {
-                    texture: texture as u32,
-                    size: (face.glyph().bitmap().width(), face.glyph().bitmap().rows()),
-                    bearing: (face.glyph().bitmap_left(), face.glyph().bitmap_top()),
-                    advance: face.glyph().advance().x as i32,
+                    texture: texture.id(),
+                    ..character.clone()
                 };
 
-                characters.insert(c as char, character);
+                characters.insert(*c, character);
+                textures.push(texture);
+            }
+
+            gl.BindTexture(gl::TEXTURE_2D, 0);
+
+            Self { gl, characters, textures }
+        }
+    }
+
+    /// GPU memory taken by the glyph textures, one byte per texel.
+    pub fn size_in_bytes(&self) -> usize {
+        self.characters
+            .values()
+            .map(|ch| (ch.size.0 * ch.size.1) as usize)
+            .sum()
+    }
+}
+
+impl TextBuilder {
+    pub fn new(text: String, font_data: &'static [u8], size: u32) -> Self {
+        TextBuilder::from_font(text, Cow::Borrowed(font_data), size)
+    }
+
+    /// Same as [`TextBuilder::new`], for fonts loaded at runtime.
+    pub fn from_bytes(text: String, font_data: Vec<u8>, size: u32) -> Self {
+        TextBuilder::from_font(text, Cow::Owned(font_data), size)
+    }
+
+    /// Reads the font file at `path`.
+    pub fn from_path(text: String, path: impl AsRef<Path>, size: u32) -> std::io::Result<Self> {
+        Ok(TextBuilder::from_bytes(text, std::fs::read(path)?, size))
+    }
+
+    fn from_font(text: String, font: Cow<'static, [u8]>, size: u32) -> Self {
+        unsafe {
+            Self {
+                text,
+                font,
+                size,
+                is_hud: false,
+                gl: std::mem::zeroed(),
+                position: nalgebra_glm::vec3(0.0, 0.0, 0.0),
+                scale: nalgebra_glm::vec3(1.0, 1.0, 1.0),
+                rotation: nalgebra_glm::quat_angle_axis(0.0, &nalgebra_glm::vec3(0.0, 0.0, 0.0)),
+                rgba: types::RGBA::new(0.1, 0.1, 0.1, 1.0),
+                glyphs: None,
+                vbo: None,
             }
+        }
+    }
 
-            let mut vbo = std::mem::zeroed();
-            gl.GenBuffers(1, &mut vbo);
-            gl.BindBuffer(gl::ARRAY_BUFFER, vbo);
+    /// `glyphs` has to be rasterized from the builder's font and size, see
+    /// [`TextBuilder::font`] and [`TextBuilder::size`].
+    #[allow(clippy::too_many_arguments)]
+    pub fn new_instance(
+        gl: gl::Gl,
+        text_builder: &TextBuilder,
+        glyphs: Rc<Glyphs>,
+        is_hud: bool, 
+        rgba: types::RGBA,
+        position: nalgebra_glm::Vec3,
+        scale: nalgebra_glm::Vec3,
+        rotation: nalgebra_glm::Quat,
+    ) -> Self {
+        unsafe {
+            let vbo = GlObject::buffer(gl.clone());
+            gl.BindBuffer(gl::ARRAY_BUFFER, vbo.id());
             gl.BufferData(
                 gl::ARRAY_BUFFER,
                 (48 * std::mem::size_of::<f32>()) as gl::types::GLsizeiptr,
@@ -106,7 +214,6 @@ impl TextBuilder {
                 gl::DYNAMIC_DRAW,
             );
             
-            gl.BindTexture(gl::TEXTURE_2D, 0);
             gl.BindBuffer(gl::ARRAY_BUFFER, 0);
 
             let cloned = text_builder.clone();
@@ -121,17 +228,30 @@ impl TextBuilder {
                 scale,
                 rotation,
                 rgba,
-                characters,
-                vbo,
+                glyphs: Some(glyphs),
+                vbo: Some(Rc::new(vbo)),
             }
         }
     }
 
-    pub fn draw(&self, program: gl::types::GLuint) {
+    pub fn font(&self) -> &[u8] {
+        &self.font
+    }
+
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    pub fn draw(&self, program: &ShaderProgram) {
         unsafe {
-            self.gl.UseProgram(program);
+            let (Some(glyphs), Some(vbo)) = (&self.glyphs, &self.vbo) else {
+                return;
+            };
+
+            program.bind();
+            let locations = program.locations();
 
-            self.gl.BindBuffer(gl::ARRAY_BUFFER, self.vbo);
+            self.gl.BindBuffer(gl::ARRAY_BUFFER, vbo.id());
             self.gl.ActiveTexture(gl::TEXTURE0);
 
             let translation_matrix =
@@ -141,63 +261,21 @@ impl TextBuilder {
 
             let model_matrix = translation_matrix * rotation_matrix * scale_matrix;
 
-            self.gl.UniformMatrix4fv(
-                self.gl
-                    .GetUniformLocation(program, b"matrix\0".as_ptr() as *const _),
-                1,
-                gl::FALSE,
-                model_matrix.as_slice().as_ptr() as *const f32,
-            );
+            program.set_mat4(locations.matrix, &model_matrix);
+            program.set_bool(locations.flip_v, false);
+            program.set_bool(locations.alpha_mask, true);
 
-            let pos_attrib = self
-                .gl
-                .GetAttribLocation(program, b"position\0".as_ptr() as *const _);
-            self.gl.VertexAttribPointer(
-                pos_attrib as gl::types::GLuint,
-                2,
-                gl::FLOAT,
-                gl::FALSE,
-                8 * std::mem::size_of::<f32>() as gl::types::GLsizei,
-                std::ptr::null(),
-            );
-            self.gl
-                .EnableVertexAttribArray(pos_attrib as gl::types::GLuint);
-
-            let color_attrib = self
-                .gl
-                .GetAttribLocation(program, b"color\0".as_ptr() as *const _);
-            self.gl.VertexAttribPointer(
-                color_attrib as gl::types::GLuint,
-                4,
-                gl::FLOAT,
-                gl::FALSE,
-                8 * std::mem::size_of::<f32>() as gl::types::GLsizei,
-                (2 * std::mem::size_of::<f32>()) as *const () as *const _,
-            );
-            self.gl
-                .EnableVertexAttribArray(color_attrib as gl::types::GLuint);
-
-            let tex_attrib = self
-                .gl
-                .GetAttribLocation(program, b"tex\0".as_ptr() as *const _);
-            self.gl.VertexAttribPointer(
-                tex_attrib as gl::types::GLuint,
-                2,
-                gl::FLOAT,
-                gl::FALSE,
-                8 * std::mem::size_of::<f32>() as gl::types::GLsizei,
-                (6 * std::mem::size_of::<f32>()) as *const () as *const _,
-            );
-            self.gl
-                .EnableVertexAttribArray(tex_attrib as gl::types::GLuint);
+            program.set_attribute(locations.position, 2, 8, 0);
+            program.set_attribute(locations.color, 4, 8, 2);
+            program.set_attribute(locations.tex, 2, 8, 6);
 
             self.gl.BindBuffer(gl::ARRAY_BUFFER, 0);
-            
+
             let mut x = self.position.x;
             let y = self.position.y;
 
             for c in self.text.chars() {
-                let ch = self.characters.get(&c).unwrap();
+                let ch = glyphs.characters.get(&c).unwrap();
 
                 let xpos = x as f32 + ch.bearing.0 as f32;
                 let ypos = y as f32 - (ch.size.1 - ch.bearing.1) as f32;
@@ -218,7 +296,7 @@ impl TextBuilder {
 
                 self.gl.BindTexture(gl::TEXTURE_2D, ch.texture);
 
-                self.gl.BindBuffer(gl::ARRAY_BUFFER, self.vbo);
+                self.gl.BindBuffer(gl::ARRAY_BUFFER, vbo.id());
                 self.gl.BufferSubData(
                     gl::ARRAY_BUFFER, 
                     0, 
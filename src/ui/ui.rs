@@ -1,5 +1,6 @@
 use crate::types;
 
+#[derive(Clone)]
 pub struct Element {
     pub el_type: types::Element,
     pub is_hud: bool,